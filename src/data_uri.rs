@@ -0,0 +1,359 @@
+/*
+ * RustyPlug - a rust module with a fluid interface for building requests to sockets
+ *
+ * Copyright (C) 2016 Steve G. Bjorg
+ *
+ * For community documentation and downloads visit mindtouch.com;
+ * please review the licensing section.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use encoding::{decode, decode_to_bytes, encode_data, DecodeError};
+use plug::Plug;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataUri {
+    media_type: String,
+    parameters: Vec<(String, String)>,
+    base64: bool,
+    data: Vec<u8>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DataUriError {
+    InvalidScheme,
+    MissingComma,
+    InvalidMediaType,
+    InvalidParameter,
+    InvalidEncoding(usize),
+    InvalidBase64(usize)
+}
+
+impl DataUri {
+    pub fn new(media_type: &str, data: Vec<u8>) -> DataUri {
+        return DataUri {
+            media_type: media_type.to_lowercase(),
+            parameters: Vec::new(),
+            base64: true,
+            data: data
+        };
+    }
+
+    pub fn parse(text: &str) -> Result<DataUri, DataUriError> {
+
+        // scheme must be 'data:'
+        match text.get(..5) {
+            Some(scheme) if scheme.eq_ignore_ascii_case("data:") => (),
+            _ => return Err(DataUriError::InvalidScheme)
+        }
+
+        // fragment is not part of the data
+        let text = match text.find('#') {
+            Some(index) => &text[..index],
+            None => text
+        };
+        return DataUri::parse_opaque_path(text, 5, None);
+    }
+
+    fn parse_opaque_path(text: &str, start: usize, query: Option<&[(String, Option<String>)]>) -> Result<DataUri, DataUriError> {
+
+        // header ends at the first ','
+        let header_end = match text[start..].find(',') {
+            Some(index) => start + index,
+            None => return Err(DataUriError::MissingComma)
+        };
+        let mut parts = text[start..header_end].split(';');
+        let mut offset = start;
+
+        // media type is optional, but must be 'type/subtype' when present
+        let media_type = parts.next().unwrap_or("");
+        let decoded_media_type = decode_header(media_type, offset)?.to_lowercase();
+        if !decoded_media_type.is_empty() && !is_media_type(&decoded_media_type) {
+            return Err(DataUriError::InvalidMediaType);
+        }
+        offset += media_type.len() + 1;
+
+        // parameters are 'name=value' pairs, optionally followed by ';base64'
+        let mut parameters = Vec::new();
+        let mut base64 = false;
+        for part in parts {
+            if base64 {
+                return Err(DataUriError::InvalidParameter);
+            }
+            if part.eq_ignore_ascii_case("base64") {
+                base64 = true;
+            } else {
+                match part.find('=') {
+                    Some(index) if index > 0 => {
+                        let name = decode_header(&part[..index], offset)?.to_lowercase();
+                        let value = decode_header(&part[(index + 1)..], offset + index + 1)?;
+                        parameters.push((name, value));
+                    },
+                    _ => return Err(DataUriError::InvalidParameter)
+                }
+            }
+            offset += part.len() + 1;
+        }
+
+        // data is percent-encoded and, optionally, base64 encoded
+        let data_start = header_end + 1;
+        let mut data = decode_to_bytes(&text[data_start..]).map_err(|error| decode_error(error, data_start))?;
+
+        // query of a plug is stored decoded, so it is appended as is
+        if let Some(query) = query {
+            data.push(b'?');
+            for (index, (key, value)) in query.iter().enumerate() {
+                if index > 0 {
+                    data.push(b'&');
+                }
+                data.extend_from_slice(key.as_bytes());
+                if let Some(ref value) = *value {
+                    data.push(b'=');
+                    data.extend_from_slice(value.as_bytes());
+                }
+            }
+        }
+        let data = if base64 {
+            decode_base64(&data)?
+        } else {
+            data
+        };
+        return Ok(DataUri {
+            media_type: decoded_media_type,
+            parameters: parameters,
+            base64: base64,
+            data: data
+        });
+    }
+
+    pub fn get_media_type(&self) -> &str {
+
+        // media type defaults to 'text/plain' (RFC 2397)
+        if self.media_type.is_empty() {
+            return "text/plain";
+        }
+        return &self.media_type;
+    }
+
+    pub fn get_parameters(&self) -> &[(String, String)] {
+        return &self.parameters;
+    }
+
+    pub fn get_parameter(&self, name: &str) -> Option<&str> {
+        return self.parameters.iter().find(|&(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str());
+    }
+
+    pub fn get_charset(&self) -> Option<&str> {
+
+        // charset defaults to 'US-ASCII' when the media type is omitted (RFC 2397)
+        match self.get_parameter("charset") {
+            Some(charset) => return Some(charset),
+            None if self.media_type.is_empty() => return Some("US-ASCII"),
+            None => return None
+        }
+    }
+
+    pub fn get_base64(&self) -> bool {
+        return self.base64;
+    }
+
+    pub fn get_data(&self) -> &[u8] {
+        return &self.data;
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        return self.data;
+    }
+
+    pub fn with_parameter(&self, name: &str, value: &str) -> DataUri {
+        let mut parameters: Vec<(String, String)> = self.parameters.iter().filter(|&(key, _)| !key.eq_ignore_ascii_case(name)).cloned().collect();
+        parameters.push((name.to_lowercase(), value.into()));
+        return DataUri { parameters: parameters, ..self.clone() };
+    }
+
+    pub fn with_charset(&self, charset: &str) -> DataUri {
+        return self.with_parameter("charset", charset);
+    }
+
+    pub fn with_base64(&self) -> DataUri {
+        return DataUri { base64: true, ..self.clone() };
+    }
+
+    pub fn without_base64(&self) -> DataUri {
+        return DataUri { base64: false, ..self.clone() };
+    }
+
+    fn to_opaque_path(&self) -> String {
+        let mut buffer = encode_header(&self.media_type);
+        for (name, value) in self.parameters.iter() {
+            buffer.push(';');
+            buffer.push_str(&encode_header(name));
+            buffer.push('=');
+            buffer.push_str(&encode_header(value));
+        }
+        if self.base64 {
+            buffer.push_str(";base64,");
+            buffer.push_str(&encode_base64(&self.data));
+        } else {
+            buffer.push(',');
+            buffer.push_str(&encode_data(&self.data));
+        }
+        return buffer;
+    }
+}
+
+impl fmt::Display for DataUri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("data:")?;
+        return f.write_str(&self.to_opaque_path());
+    }
+}
+
+impl FromStr for DataUri {
+    type Err = DataUriError;
+
+    fn from_str(text: &str) -> Result<DataUri, DataUriError> {
+        return DataUri::parse(text);
+    }
+}
+
+impl<'a> TryFrom<&'a Plug> for DataUri {
+    type Error = DataUriError;
+
+    fn try_from(plug: &'a Plug) -> Result<DataUri, DataUriError> {
+        if !plug.get_scheme().eq_ignore_ascii_case("data") {
+            return Err(DataUriError::InvalidScheme);
+        }
+
+        // payload comes from the stored opaque path and query, so nothing is encoded a second time
+        match plug.get_opaque_path() {
+            Some(path) => {
+                let text = format!("{}:{}", plug.get_scheme(), path);
+                return DataUri::parse_opaque_path(&text, plug.get_scheme().len() + 1, plug.get_query().as_deref());
+            },
+            None => return DataUri::parse(&plug.to_string())
+        }
+    }
+}
+
+impl<'a> From<&'a DataUri> for Plug {
+    fn from(uri: &'a DataUri) -> Plug {
+        return Plug::from_opaque_path("data".into(), uri.to_opaque_path());
+    }
+}
+
+impl fmt::Display for DataUriError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DataUriError::InvalidScheme => return f.write_str("scheme is not 'data:'"),
+            DataUriError::MissingComma => return f.write_str("missing ',' before data"),
+            DataUriError::InvalidMediaType => return f.write_str("invalid media type"),
+            DataUriError::InvalidParameter => return f.write_str("invalid media type parameter"),
+            DataUriError::InvalidEncoding(offset) => return write!(f, "invalid percent-encoding at offset {}", offset),
+            DataUriError::InvalidBase64(offset) => return write!(f, "invalid base64 data at byte {}", offset)
+        }
+    }
+}
+
+impl Error for DataUriError {}
+
+fn is_media_type(text: &str) -> bool {
+    let mut parts = text.split('/');
+    return match (parts.next(), parts.next(), parts.next()) {
+        (Some(kind), Some(subtype), None) => is_token(kind) && is_token(subtype),
+        _ => false
+    };
+}
+
+fn is_token(text: &str) -> bool {
+
+    // token characters (RFC 2045)
+    return !text.is_empty() && text.bytes().all(|b| (b > b' ') && (b < 0x7F) && !b"()<>@,;:\\\"/[]?=".contains(&b));
+}
+
+fn decode_header(text: &str, start: usize) -> Result<String, DataUriError> {
+    return decode(text).map_err(|error| decode_error(error, start));
+}
+
+fn decode_error(error: DecodeError, start: usize) -> DataUriError {
+    match error {
+        DecodeError::InvalidEscape(offset) => return DataUriError::InvalidEncoding(start + offset),
+        DecodeError::InvalidUtf8 => return DataUriError::InvalidEncoding(start)
+    }
+}
+
+fn encode_header(text: &str) -> String {
+
+    // ';', ',' and '=' delimit the header parts, so they are always encoded
+    return encode_data(text.as_bytes()).replace(';', "%3B").replace(',', "%2C").replace('=', "%3D");
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode_base64(data: &[u8]) -> String {
+    let mut buffer = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], chunk.get(1).cloned().unwrap_or(0), chunk.get(2).cloned().unwrap_or(0)];
+        let value = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | (b[2] as u32);
+        for index in 0..4 {
+            if index <= chunk.len() {
+                buffer.push(BASE64[((value >> (18 - (index * 6))) & 0x3F) as usize] as char);
+            } else {
+                buffer.push('=');
+            }
+        }
+    }
+    return buffer;
+}
+
+fn decode_base64(data: &[u8]) -> Result<Vec<u8>, DataUriError> {
+
+    // padding is optional, but can only appear at the end
+    let end = data.iter().rposition(|&b| b != b'=').map_or(0, |index| index + 1);
+    if (data.len() - end) > 2 {
+        return Err(DataUriError::InvalidBase64(end + 2));
+    }
+    let mut buffer = Vec::with_capacity((end * 3) / 4);
+    let mut accumulator: u32 = 0;
+    let mut bits = 0;
+    for (index, &b) in data[..end].iter().enumerate() {
+        let value = match b {
+            b'A'..=b'Z' => b - b'A',
+            b'a'..=b'z' => b - b'a' + 26,
+            b'0'..=b'9' => b - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return Err(DataUriError::InvalidBase64(index))
+        };
+        accumulator = (accumulator << 6) | (value as u32);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            buffer.push((accumulator >> bits) as u8);
+            accumulator &= (1 << bits) - 1;
+        }
+    }
+
+    // a single trailing character cannot encode a full byte
+    if (end % 4) == 1 {
+        return Err(DataUriError::InvalidBase64(end - 1));
+    }
+    return Ok(buffer);
+}
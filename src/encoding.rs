@@ -0,0 +1,314 @@
+/*
+ * RustyPlug - a rust module with a fluid interface for building requests to sockets
+ *
+ * Copyright (C) 2016 Steve G. Bjorg
+ *
+ * For community documentation and downloads visit mindtouch.com;
+ * please review the licensing section.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#![allow(clippy::needless_return)]
+
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DecodeError {
+    InvalidEscape(usize),
+    InvalidUtf8
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecodeError::InvalidEscape(offset) => return write!(f, "invalid escape sequence at offset {}", offset),
+            DecodeError::InvalidUtf8 => return f.write_str("decoded text is not valid UTF-8")
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+pub fn decode(text: &str) -> Result<String, DecodeError> {
+    return decode_bytes(text, false);
+}
+
+pub fn decode_form(text: &str) -> Result<String, DecodeError> {
+
+    // '+' is a legacy encoding for ' ' in form bodies; generic query strings keep it as is
+    return decode_bytes(text, true);
+}
+
+pub fn decode_to_bytes(text: &str) -> Result<Vec<u8>, DecodeError> {
+    return decode_raw(text, false);
+}
+
+pub fn encode_userinfo(text: &str) -> String {
+    return encode(text, is_userinfo_byte, false);
+}
+
+pub fn encode_host(text: &str) -> String {
+
+    // IP literals are kept as-is, since their brackets and colons are part of the syntax
+    if text.starts_with('[') && text.ends_with(']') {
+        return text.into();
+    }
+    return encode(text, is_host_byte, false);
+}
+
+pub fn encode_zone_id(text: &str) -> String {
+    return encode(text, is_unreserved_byte, false);
+}
+
+pub fn encode_socket_path(text: &str) -> String {
+
+    // '/' would end the authority, so everything but unreserved characters is encoded
+    return encode(text, is_unreserved_byte, false);
+}
+
+pub fn encode_segment(text: &str) -> String {
+    return encode(text, is_segment_byte, false);
+}
+
+pub fn encode_segment_name(text: &str) -> String {
+    return encode(text, is_segment_name_byte, false);
+}
+
+pub fn encode_segment_param_key(text: &str) -> String {
+    return encode(text, is_segment_param_key_byte, false);
+}
+
+pub fn encode_segment_param_value(text: &str) -> String {
+    return encode(text, is_segment_name_byte, false);
+}
+
+pub fn encode_opaque_path(text: &str) -> String {
+    return encode(text, is_opaque_path_byte, false);
+}
+
+pub fn encode_data(bytes: &[u8]) -> String {
+    return encode_raw(bytes, is_opaque_path_byte, false);
+}
+
+pub fn encode_query_key(text: &str) -> String {
+    return encode(text, is_query_key_byte, false);
+}
+
+pub fn encode_query_value(text: &str) -> String {
+    return encode(text, is_query_value_byte, false);
+}
+
+pub fn encode_form_key(text: &str) -> String {
+    return encode(text, is_query_key_byte, true);
+}
+
+pub fn encode_form_value(text: &str) -> String {
+    return encode(text, is_query_value_byte, true);
+}
+
+pub fn encode_fragment(text: &str) -> String {
+    return encode(text, is_fragment_byte, false);
+}
+
+pub fn encode_unreserved(text: &str) -> String {
+    return encode(text, is_unreserved_byte, false);
+}
+
+pub fn encode_reserved(text: &str) -> String {
+
+    // reserved characters and existing escapes pass through (RFC 6570, section 3.2.3)
+    let bytes = text.as_bytes();
+    let mut buffer = String::with_capacity(text.len());
+    let mut start = 0;
+    let mut index = 0;
+    while index < bytes.len() {
+        match (bytes[index], bytes.get(index + 1).and_then(|&b| hex_value(b)), bytes.get(index + 2).and_then(|&b| hex_value(b))) {
+            (b'%', Some(_), Some(_)) => {
+                buffer.push_str(&encode(&text[start..index], is_reserved_byte, false));
+                buffer.push_str(&text[index..(index + 3)]);
+                index += 3;
+                start = index;
+            },
+            _ => index += 1
+        }
+    }
+    buffer.push_str(&encode(&text[start..], is_reserved_byte, false));
+    return buffer;
+}
+
+pub fn normalize_escapes(text: &str) -> String {
+    return rewrite_escapes(text, true, true);
+}
+
+pub fn uppercase_escapes(text: &str) -> String {
+    return rewrite_escapes(text, true, false);
+}
+
+pub fn decode_unreserved(text: &str) -> String {
+    return rewrite_escapes(text, false, true);
+}
+
+fn rewrite_escapes(text: &str, uppercase: bool, decode_unreserved: bool) -> String {
+
+    // escapes of unreserved characters can be decoded and the others can get uppercase hex digits (RFC 3986, 6.2.2)
+    let bytes = text.as_bytes();
+    let mut buffer = String::with_capacity(text.len());
+    let mut start = 0;
+    let mut index = 0;
+    while index < bytes.len() {
+        match (bytes[index], bytes.get(index + 1).and_then(|&b| hex_value(b)), bytes.get(index + 2).and_then(|&b| hex_value(b))) {
+            (b'%', Some(high), Some(low)) => {
+                buffer.push_str(&text[start..index]);
+                let b = (high << 4) | low;
+                if decode_unreserved && is_unreserved_byte(b) {
+                    buffer.push(b as char);
+                } else if uppercase {
+                    buffer.push_str(&format!("%{:02X}", b));
+                } else {
+                    buffer.push_str(&text[index..(index + 3)]);
+                }
+                index += 3;
+                start = index;
+            },
+            _ => index += 1
+        }
+    }
+    buffer.push_str(&text[start..]);
+    return buffer;
+}
+
+fn decode_bytes(text: &str, plus_as_space: bool) -> Result<String, DecodeError> {
+    return String::from_utf8(decode_raw(text, plus_as_space)?).map_err(|_| DecodeError::InvalidUtf8);
+}
+
+fn decode_raw(text: &str, plus_as_space: bool) -> Result<Vec<u8>, DecodeError> {
+    let bytes = text.as_bytes();
+    let mut buffer = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'%' => {
+
+                // escape sequence must be followed by two hex digits
+                let high = bytes.get(index + 1).and_then(|&b| hex_value(b));
+                let low = bytes.get(index + 2).and_then(|&b| hex_value(b));
+                match (high, low) {
+                    (Some(high), Some(low)) => buffer.push((high << 4) | low),
+                    _ => return Err(DecodeError::InvalidEscape(index))
+                }
+                index += 3;
+            },
+            b'+' if plus_as_space => {
+                buffer.push(b' ');
+                index += 1;
+            },
+            b => {
+                buffer.push(b);
+                index += 1;
+            }
+        }
+    }
+    return Ok(buffer);
+}
+
+fn encode(text: &str, is_safe: fn(u8) -> bool, space_as_plus: bool) -> String {
+    return encode_raw(text.as_bytes(), is_safe, space_as_plus);
+}
+
+fn encode_raw(bytes: &[u8], is_safe: fn(u8) -> bool, space_as_plus: bool) -> String {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+    let mut buffer = String::with_capacity(bytes.len());
+    for &b in bytes {
+        if is_safe(b) {
+            buffer.push(b as char);
+        } else if space_as_plus && (b == b' ') {
+            buffer.push('+');
+        } else {
+            buffer.push('%');
+            buffer.push(HEX[(b >> 4) as usize] as char);
+            buffer.push(HEX[(b & 0x0F) as usize] as char);
+        }
+    }
+    return buffer;
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    return match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None
+    };
+}
+
+fn is_unreserved_byte(b: u8) -> bool {
+    return b.is_ascii_alphanumeric()
+        || (b == b'-')
+        || (b == b'.')
+        || (b == b'_')
+        || (b == b'~');
+}
+
+fn is_sub_delim_byte(b: u8) -> bool {
+    return matches!(b, b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'=');
+}
+
+fn is_reserved_byte(b: u8) -> bool {
+
+    // unreserved characters are included, since they never need an escape either
+    return is_unreserved_byte(b) || is_sub_delim_byte(b) || matches!(b, b':' | b'/' | b'?' | b'#' | b'[' | b']' | b'@');
+}
+
+fn is_userinfo_byte(b: u8) -> bool {
+
+    // ':' separates the username from the password, so it is always encoded
+    return is_unreserved_byte(b) || is_sub_delim_byte(b);
+}
+
+fn is_host_byte(b: u8) -> bool {
+    return is_unreserved_byte(b) || is_sub_delim_byte(b);
+}
+
+fn is_segment_byte(b: u8) -> bool {
+    return is_unreserved_byte(b) || is_sub_delim_byte(b) || (b == b':') || (b == b'@');
+}
+
+fn is_segment_name_byte(b: u8) -> bool {
+
+    // ';' starts a matrix parameter, so it is always encoded
+    return is_segment_byte(b) && (b != b';');
+}
+
+fn is_segment_param_key_byte(b: u8) -> bool {
+    return is_segment_name_byte(b) && (b != b'=');
+}
+
+fn is_opaque_path_byte(b: u8) -> bool {
+    return is_segment_byte(b) || (b == b'/');
+}
+
+fn is_query_key_byte(b: u8) -> bool {
+    return is_query_value_byte(b) && (b != b'=');
+}
+
+fn is_query_value_byte(b: u8) -> bool {
+
+    // '&' separates parameters and '+' reads as ' ' in form bodies, so both are always encoded
+    return is_fragment_byte(b) && (b != b'&') && (b != b'+');
+}
+
+fn is_fragment_byte(b: u8) -> bool {
+    return is_segment_byte(b) || (b == b'/') || (b == b'?');
+}
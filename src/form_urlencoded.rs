@@ -0,0 +1,114 @@
+/*
+ * RustyPlug - a rust module with a fluid interface for building requests to sockets
+ *
+ * Copyright (C) 2016 Steve G. Bjorg
+ *
+ * For community documentation and downloads visit mindtouch.com;
+ * please review the licensing section.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#![allow(clippy::needless_return)]
+
+use encoding::{decode, decode_form, encode_form_key, encode_form_value, encode_query_key, encode_query_value, DecodeError};
+use uri_parser::Params;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Separator {
+    Ampersand,
+    Semicolon
+}
+
+pub fn parse(text: &str) -> Result<Vec<(String, Option<String>)>, DecodeError> {
+    return parse_with(text, Separator::Ampersand);
+}
+
+pub fn parse_with(text: &str, separator: Separator) -> Result<Vec<(String, Option<String>)>, DecodeError> {
+    return parse_pairs(text, separator, true).map_err(|(start, _, error)| {
+        match error {
+            DecodeError::InvalidEscape(index) => return DecodeError::InvalidEscape(start + index),
+            error => return error
+        }
+    });
+}
+
+pub fn serialize(params: &[(String, Option<String>)]) -> String {
+    return serialize_with(params, Separator::Ampersand);
+}
+
+pub fn serialize_with(params: &[(String, Option<String>)], separator: Separator) -> String {
+    return serialize_pairs(params, separator, true);
+}
+
+impl Separator {
+    pub fn as_char(self) -> char {
+        match self {
+            Separator::Ampersand => return '&',
+            Separator::Semicolon => return ';'
+        }
+    }
+}
+
+pub(crate) fn parse_pairs(text: &str, separator: Separator, plus_as_space: bool) -> Result<Params, (usize, usize, DecodeError)> {
+
+    // empty text has no parameters, but empty parameters between separators are kept;
+    // '+' is a space in form bodies only, generic query strings keep it as is
+    let decode_part = if plus_as_space { decode_form } else { decode };
+    let mut params = Vec::new();
+    if text.is_empty() {
+        return Ok(params);
+    }
+    let mut start = 0;
+    for param in text.split(separator.as_char()) {
+
+        // part before the first '=' is the key; without '=' the parameter has no value
+        let end = start + param.len();
+        match param.find('=') {
+            Some(index) => {
+                let key = decode_part(&param[..index]).map_err(|error| (start, start + index, error))?;
+                let value = decode_part(&param[(index + 1)..]).map_err(|error| (start + index + 1, end, error))?;
+                params.push((key, Some(value)));
+            },
+            None => params.push((decode_part(param).map_err(|error| (start, end, error))?, None))
+        }
+        start = end + 1;
+    }
+    return Ok(params);
+}
+
+pub(crate) fn serialize_pairs(params: &[(String, Option<String>)], separator: Separator, space_as_plus: bool) -> String {
+    let encode_key = if space_as_plus { encode_form_key } else { encode_query_key };
+    let encode_value = if space_as_plus { encode_form_value } else { encode_query_value };
+    let mut buffer = String::new();
+    for (index, (key, optional_value)) in params.iter().enumerate() {
+        if index > 0 {
+            buffer.push(separator.as_char());
+        }
+        push_encoded(&mut buffer, &encode_key(key), separator);
+        if let Some(ref value) = *optional_value {
+            buffer.push('=');
+            push_encoded(&mut buffer, &encode_value(value), separator);
+        }
+    }
+    return buffer;
+}
+
+fn push_encoded(buffer: &mut String, text: &str, separator: Separator) {
+
+    // ';' is safe in query strings, except when it separates parameters
+    match separator {
+        Separator::Ampersand => buffer.push_str(text),
+        Separator::Semicolon => buffer.push_str(&text.replace(';', "%3B"))
+    }
+}
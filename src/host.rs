@@ -0,0 +1,113 @@
+/*
+ * RustyPlug - a rust module with a fluid interface for building requests to sockets
+ *
+ * Copyright (C) 2016 Steve G. Bjorg
+ *
+ * For community documentation and downloads visit mindtouch.com;
+ * please review the licensing section.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#![allow(clippy::needless_return)]
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use idna;
+use encoding::{encode_host, encode_socket_path, encode_zone_id};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Host {
+    Domain(String),
+    Ipv4(Ipv4Addr),
+    Ipv6(Ipv6Addr, Option<String>),
+    UnixSocket(PathBuf)
+}
+
+impl Host {
+    pub fn get_ip(&self) -> Option<IpAddr> {
+        match *self {
+            Host::Domain(_) | Host::UnixSocket(_) => return None,
+            Host::Ipv4(address) => return Some(IpAddr::V4(address)),
+            Host::Ipv6(address, _) => return Some(IpAddr::V6(address))
+        }
+    }
+
+    pub fn to_unicode(&self) -> String {
+        match *self {
+
+            // punycode labels are shown in their Unicode form (UTS #46)
+            Host::Domain(ref name) => return idna::domain_to_unicode(name).0,
+            _ => return self.to_string()
+        }
+    }
+
+    pub fn get_socket_path(&self) -> Option<&Path> {
+        match *self {
+            Host::UnixSocket(ref path) => return Some(path),
+            _ => return None
+        }
+    }
+
+    pub fn get_zone_id(&self) -> Option<&str> {
+        match *self {
+            Host::Ipv6(_, Some(ref zone_id)) => return Some(zone_id),
+            _ => return None
+        }
+    }
+}
+
+impl fmt::Display for Host {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Host::Domain(ref name) => {
+
+                // hostname with non-ASCII characters is written in punycode when possible
+                if !name.is_ascii() {
+                    if let Ok(ascii) = idna::domain_to_ascii(name) {
+                        return f.write_str(&ascii);
+                    }
+                }
+                return f.write_str(&encode_host(name));
+            },
+            Host::Ipv4(ref address) => return write!(f, "{}", address),
+            Host::Ipv6(ref address, None) => return write!(f, "[{}]", address),
+
+            // zone identifier is separated by an encoded '%' (RFC 6874)
+            Host::Ipv6(ref address, Some(ref zone_id)) => return write!(f, "[{}%25{}]", address, encode_zone_id(zone_id)),
+            Host::UnixSocket(ref path) => return f.write_str(&encode_socket_path(&path.to_string_lossy()))
+        }
+    }
+}
+
+impl From<Ipv4Addr> for Host {
+    fn from(address: Ipv4Addr) -> Host {
+        return Host::Ipv4(address);
+    }
+}
+
+impl From<Ipv6Addr> for Host {
+    fn from(address: Ipv6Addr) -> Host {
+        return Host::Ipv6(address, None);
+    }
+}
+
+impl From<IpAddr> for Host {
+    fn from(address: IpAddr) -> Host {
+        match address {
+            IpAddr::V4(address) => return Host::Ipv4(address),
+            IpAddr::V6(address) => return Host::Ipv6(address, None)
+        }
+    }
+}
@@ -19,8 +19,34 @@
  * limitations under the License.
  */
 
+extern crate idna;
+#[cfg(any(feature = "serde", test))]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_urlencoded;
+extern crate zeroize;
+#[cfg(feature = "url")]
+extern crate url;
+#[cfg(feature = "http")]
+extern crate http;
+#[cfg(test)]
+extern crate serde_json;
+#[cfg(test)]
+extern crate proptest;
+
 mod uri_parser;
+pub mod data_uri;
+pub mod encoding;
+pub mod form_urlencoded;
+pub mod host;
 pub mod plug;
+pub mod plug_pattern;
+pub mod plug_ref;
+pub mod scheme;
+pub mod secret;
+pub mod uri_template;
+#[cfg(unix)]
+pub mod transport;
 
 #[cfg(test)]
 mod tests;
@@ -19,112 +19,658 @@
  * limitations under the License.
  */
 
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::slice;
+use std::str::FromStr;
+use std::vec;
+use std::net::{IpAddr, SocketAddr, SocketAddrV6, ToSocketAddrs};
+use std::path::{Component, Path, PathBuf, Prefix, MAIN_SEPARATOR};
+use encoding::{uppercase_escapes, decode_unreserved, encode_userinfo, encode_segment_name, encode_segment_param_key, encode_segment_param_value, encode_opaque_path, encode_fragment};
+use form_urlencoded::{self, Separator};
 use uri_parser::*;
+use host::Host;
+use scheme;
+use secret::Secret;
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "serde")]
+use serde_urlencoded;
+#[cfg(feature = "url")]
+use url;
+#[cfg(feature = "http")]
+use http;
+
+pub use uri_parser::{Segment, UriComponent, UriErrorKind};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum PlugCredentials {
     None,
     Username(String),
-    UsernamePassword(String, String)
+    UsernamePassword(String, Secret)
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
 pub struct Plug {
     scheme: String,
     credentials: PlugCredentials,
-    host: String,
+    host: Host,
     port: Option<u16>,
-    segments: Vec<String>,
+    segments: Vec<Segment>,
     query: Option<Vec<(String, Option<String>)>>,
     fragment: Option<String>,
-    trailing_slash: bool
+    trailing_slash: bool,
+    opaque: Option<String>
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlugNormalization {
+    lowercase_scheme_and_host: bool,
+    drop_default_port: bool,
+    remove_dot_segments: bool,
+    uppercase_escapes: bool,
+    decode_unreserved: bool
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub enum PlugParserError {
-    InternalError,
+pub struct PlugParserError {
+    kind: UriErrorKind,
+    component: UriComponent,
+    offset: usize,
+    found: Option<char>
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlugFilePathError {
+    RelativePath,
+    NonUnicodePath,
     InvalidScheme,
-    InvalidHostname,
-    InvalidIPv6,
-    InvalidPortNumber,
-    MissingColonSlashSlash
+    InvalidHost,
+    InvalidSegment
 }
 
 impl Plug {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         scheme: String,
         credentials: PlugCredentials,
-        host: String,
+        host: Host,
         port: Option<u16>,
-        segments: Vec<String>,
+        segments: Vec<Segment>,
         query: Option<Vec<(String, Option<String>)>>,
         fragment: Option<String>,
         trailing_slash: bool
     ) -> Plug {
+
+        // components are stored in the form Plug::parse produces, so the plug round-trips through its string
+        let (segments, trailing_slash) = canonical_path(segments, trailing_slash);
         return Plug {
             scheme: scheme,
             credentials: credentials,
-            host: host,
+            host: canonical_host(host),
             port: port,
             segments: segments,
-            query: query,
+            query: query.map(canonical_query),
             fragment: fragment,
-            trailing_slash: trailing_slash
+            trailing_slash: trailing_slash,
+            opaque: None
+        };
+    }
+
+    pub fn new_opaque(scheme: String, path: &str) -> Plug {
+        return Plug::from_opaque_path(scheme, encode_canonical_opaque_path(path));
+    }
+
+    pub(crate) fn from_opaque_path(scheme: String, opaque: String) -> Plug {
+
+        // opaque path must already be encoded
+        return Plug {
+            scheme: scheme,
+            credentials: PlugCredentials::None,
+            host: Host::Domain(String::new()),
+            port: None,
+            segments: Vec::new(),
+            query: None,
+            fragment: None,
+            trailing_slash: false,
+            opaque: Some(opaque)
         };
     }
 
+    pub fn from_socket_addr(scheme: String, address: SocketAddr) -> Plug {
+
+        // non-zero IPv6 scope id becomes the zone id
+        let host = match address {
+            SocketAddr::V4(address) => Host::Ipv4(*address.ip()),
+            SocketAddr::V6(address) if address.scope_id() != 0 => Host::Ipv6(*address.ip(), Some(address.scope_id().to_string())),
+            SocketAddr::V6(address) => Host::Ipv6(*address.ip(), None)
+        };
+        return Plug::new(scheme, PlugCredentials::None, host, Some(address.port()), Vec::new(), None, None, false);
+    }
+
+    pub fn from_file_path(path: &Path) -> Result<Plug, PlugFilePathError> {
+
+        // only absolute paths can be expressed as a 'file://' URI
+        if !path.is_absolute() {
+            return Err(PlugFilePathError::RelativePath);
+        }
+        let mut host = String::new();
+        let mut segments = Vec::new();
+        for component in path.components() {
+            match component {
+                Component::Prefix(prefix) => {
+                    match prefix.kind() {
+
+                        // drive letter becomes the first segment (e.g. 'file:///C:/Windows')
+                        Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => segments.push(Segment::new(format!("{}:", letter as char))),
+
+                        // UNC server becomes the host and the share becomes the first segment
+                        Prefix::UNC(server, share) | Prefix::VerbatimUNC(server, share) => {
+                            host = server.to_str().ok_or(PlugFilePathError::NonUnicodePath)?.into();
+                            segments.push(Segment::from(share.to_str().ok_or(PlugFilePathError::NonUnicodePath)?));
+                        },
+                        _ => return Err(PlugFilePathError::InvalidHost)
+                    }
+                },
+                Component::RootDir | Component::CurDir => (),
+                Component::ParentDir => {
+                    segments.pop();
+                },
+                Component::Normal(segment) => segments.push(Segment::from(segment.to_str().ok_or(PlugFilePathError::NonUnicodePath)?))
+            }
+        }
+
+        // root directory is written as 'file:///'
+        let trailing_slash = segments.is_empty();
+        return Ok(Plug::new("file".into(), PlugCredentials::None, Host::Domain(host), None, segments, None, None, trailing_slash));
+    }
+
+    pub fn to_file_path(&self) -> Result<PathBuf, PlugFilePathError> {
+        if !self.scheme.eq_ignore_ascii_case("file") {
+            return Err(PlugFilePathError::InvalidScheme);
+        }
+        if self.opaque.is_some() {
+            return Err(PlugFilePathError::RelativePath);
+        }
+        if (self.credentials != PlugCredentials::None) || self.port.is_some() {
+            return Err(PlugFilePathError::InvalidHost);
+        }
+
+        // dot segments are resolved before the path is assembled
+        let (segments, trailing_slash) = split_path(remove_dot_segments(join_path(self.segments.clone(), self.trailing_slash)));
+        let segments: Vec<String> = segments.iter().map(segment_text).collect();
+        let separator = MAIN_SEPARATOR.to_string();
+        if segments.iter().any(|segment| segment.contains('/') || segment.contains(MAIN_SEPARATOR) || segment.contains('\0')) {
+            return Err(PlugFilePathError::InvalidSegment);
+        }
+        let mut buffer = String::new();
+        match self.host {
+
+            // empty host and 'localhost' both refer to the local machine
+            Host::Domain(ref name) if name.is_empty() || name.eq_ignore_ascii_case("localhost") => {
+                if cfg!(windows) && segments.first().is_some_and(|segment| is_drive_letter(segment)) {
+                    buffer.push_str(&segments.join(&separator));
+                    if segments.len() == 1 {
+                        buffer.push_str(&separator);
+                    }
+                } else {
+                    buffer.push_str(&separator);
+                    buffer.push_str(&segments.join(&separator));
+                }
+            },
+
+            // remote host is only reachable as a UNC path
+            ref host if cfg!(windows) => {
+                buffer.push_str(&separator);
+                buffer.push_str(&separator);
+                buffer.push_str(&host.to_string());
+                buffer.push_str(&separator);
+                buffer.push_str(&segments.join(&separator));
+            },
+            _ => return Err(PlugFilePathError::InvalidHost)
+        }
+        if trailing_slash && !buffer.ends_with(MAIN_SEPARATOR) {
+            buffer.push_str(&separator);
+        }
+        return Ok(PathBuf::from(buffer));
+    }
+
     pub fn parse(uri: &str) -> Result<Plug, PlugParserError> {
-        let mut parser = uri.chars().peekable();
-        let scheme = match parse_scheme(&mut parser) {
-            Ok(value) => value,
-            Err(UriParserError::InternalError) => return Err(PlugParserError::InternalError),
-            Err(UriParserError::InvalidScheme) => return Err(PlugParserError::InvalidScheme),
-            Err(UriParserError::InvalidHostname) => return Err(PlugParserError::InvalidHostname),
-            Err(UriParserError::InvalidIPv6) => return Err(PlugParserError::InvalidIPv6),
-            Err(UriParserError::InvalidPortNumber) => return Err(PlugParserError::InvalidPortNumber)
-        };
-        if (Some(':') != parser.next()) || (Some(':') != parser.next()) || (Some(':') != parser.next()) {
-            return Err(PlugParserError::MissingColonSlashSlash);
-        }
-        let (credentials, host, port) = match parse_authority(&mut parser) {
-            Ok(value) => value,
-            Err(UriParserError::InternalError) => return Err(PlugParserError::InternalError),
-            Err(UriParserError::InvalidScheme) => return Err(PlugParserError::InvalidScheme),
-            Err(UriParserError::InvalidHostname) => return Err(PlugParserError::InvalidHostname),
-            Err(UriParserError::InvalidIPv6) => return Err(PlugParserError::InvalidIPv6),
-            Err(UriParserError::InvalidPortNumber) => return Err(PlugParserError::InvalidPortNumber)
+        let mut parser = UriChars::new(uri);
+        let scheme = parse_scheme(&mut parser)?;
+
+        // scheme followed by ':', but not by '//' or '/', has an opaque path (e.g. 'mailto:', 'urn:')
+        let mut lookahead = parser.clone();
+        if (Some(':') == lookahead.next()) && is_opaque_path_start(&lookahead) && !requires_authority(&scheme) {
+            let opaque = parse_opaque_path(&mut lookahead)?;
+            let query = parse_query(&mut lookahead)?;
+            let fragment = parse_fragment(&mut lookahead)?;
+            return Ok(Plug {
+                query: query,
+                fragment: fragment,
+                ..Plug::from_opaque_path(scheme, opaque)
+            });
+        }
+        for expected in "://".chars() {
+            if Some(&expected) != parser.peek() {
+                return Err(PlugParserError {
+                    kind: UriErrorKind::MissingColonSlashSlash,
+                    component: UriComponent::Scheme,
+                    offset: parser.offset(),
+                    found: parser.peek().cloned()
+                });
+            }
+            parser.next();
+        }
+        let authority_start = parser.offset();
+        let (credentials, host, port) = if is_unix_socket_scheme(&scheme) {
+            parse_socket_authority(&mut parser)?
+        } else {
+            parse_authority(&mut parser)?
         };
+        if (Host::Domain(String::new()) == host) && requires_authority(&scheme) {
+            return Err(PlugParserError {
+                kind: UriErrorKind::InvalidHostname,
+                component: UriComponent::Host,
+                offset: authority_start,
+                found: uri[authority_start..].chars().next()
+            });
+        }
+        let (segments, trailing_slash) = parse_path(&mut parser)?;
+        let query = parse_query(&mut parser)?;
+        let fragment = parse_fragment(&mut parser)?;
         return Ok(Plug {
             scheme: scheme,
-            credentials: match credentials {
-                UriCredentials::None => PlugCredentials::None,
-                UriCredentials::Username(username) => PlugCredentials::Username(username),
-                UriCredentials::UsernamePassword(username, password) => PlugCredentials::UsernamePassword(username, password)
-            },
+            credentials: credentials.into(),
             host: host,
             port: port,
-
-            // TODO
-            segments: vec![],
-            query: None,
-            fragment: None,
-            trailing_slash: false,
+            segments: segments,
+            query: query,
+            fragment: fragment,
+            trailing_slash: trailing_slash,
+            opaque: None
         });
     }
 
+    pub fn resolve(&self, reference: &str) -> Result<Plug, PlugParserError> {
+        let text = reference;
+        let mut parser = UriChars::new(text);
+        let reference = parse_reference(&mut parser, &self.scheme)?;
+
+        // reference with the base scheme but no authority is resolved without its scheme (RFC 3986, 5.2.2, non-strict)
+        if let Some(ref scheme) = reference.scheme {
+            if reference.authority.is_none() && self.opaque.is_none() && scheme.eq_ignore_ascii_case(&self.scheme) {
+                return self.resolve(&text[(scheme.len() + 1)..]);
+            }
+        }
+        let reference_path = join_path(reference.segments, reference.trailing_slash);
+        let mut result = self.clone();
+        result.fragment = reference.fragment;
+
+        // scheme that requires an authority cannot have an opaque path
+        let opaque = match reference.scheme {
+            Some(ref scheme) if requires_authority(scheme) => None,
+            _ => reference.opaque
+        };
+        let path = if let Some(opaque) = opaque {
+
+            // reference with an opaque path replaces the base entirely
+            return Ok(Plug {
+                query: reference.query,
+                fragment: result.fragment,
+                ..Plug::from_opaque_path(reference.scheme.unwrap_or_default(), opaque)
+            });
+        } else if let Some(scheme) = reference.scheme {
+
+            // reference with a scheme replaces the base entirely
+            let (credentials, host, port) = match reference.authority {
+                Some(authority) => authority,
+                None => {
+                    let offset = scheme.len() + 1;
+                    return Err(PlugParserError {
+                        kind: UriErrorKind::MissingColonSlashSlash,
+                        component: UriComponent::Scheme,
+                        offset: offset,
+                        found: text[offset..].chars().next()
+                    });
+                }
+            };
+            result.scheme = scheme;
+            result.opaque = None;
+            result.credentials = credentials.into();
+            result.host = host;
+            result.port = port;
+            result.query = reference.query;
+            remove_dot_segments(reference_path)
+        } else if let Some((credentials, host, port)) = reference.authority {
+
+            // reference with an authority only keeps the base scheme
+            result.opaque = None;
+            result.credentials = credentials.into();
+            result.host = host;
+            result.port = port;
+            result.query = reference.query;
+            remove_dot_segments(reference_path)
+        } else if reference_path.is_empty() {
+
+            // reference without a path keeps the base path and, unless it has its own, the base query
+            if reference.query.is_some() {
+                result.query = reference.query;
+            }
+            return Ok(result);
+        } else if self.opaque.is_some() {
+
+            // path cannot be resolved against an opaque path
+            return Err(PlugParserError {
+                kind: UriErrorKind::InvalidPath,
+                component: UriComponent::Path,
+                offset: 0,
+                found: text.chars().next()
+            });
+        } else if reference.absolute {
+            result.query = reference.query;
+            remove_dot_segments(reference_path)
+        } else {
+
+            // relative path replaces the last segment of the base path
+            let mut path = join_path(self.segments.clone(), self.trailing_slash);
+            path.pop();
+            path.extend(reference_path);
+            result.query = reference.query;
+            remove_dot_segments(path)
+        };
+
+        // authority from the reference must be one the scheme can parse back
+        if (Host::Domain(String::new()) == result.host) && requires_authority(&result.scheme) {
+            let offset = text.find("//").map_or(0, |index| index + 2);
+            return Err(PlugParserError {
+                kind: UriErrorKind::InvalidHostname,
+                component: UriComponent::Host,
+                offset: offset,
+                found: text[offset..].chars().next()
+            });
+        }
+        return Ok(result.with_path_segments(path, false));
+    }
+
+    pub fn normalize(&self) -> Plug {
+
+        // each scheme has its own normalization policy
+        let normalization = scheme::lookup(&self.scheme).map(|scheme| scheme.get_normalization()).unwrap_or_default();
+        return self.normalize_with(normalization);
+    }
+
+    pub fn normalize_with(&self, normalization: PlugNormalization) -> Plug {
+
+        // hierarchical components are stored decoded and always rendered with uppercase escapes for reserved
+        // characters only, but an opaque path is stored as written, so only its escapes are rewritten here
+        let mut result = self.clone();
+        if let Some(ref opaque) = self.opaque {
+            let mut opaque = opaque.clone();
+            if normalization.uppercase_escapes {
+                opaque = uppercase_escapes(&opaque);
+            }
+            if normalization.decode_unreserved {
+                opaque = decode_unreserved(&opaque);
+            }
+            result.opaque = Some(opaque);
+        }
+        if normalization.lowercase_scheme_and_host {
+            result.scheme = result.scheme.to_lowercase();
+            if let Host::Domain(ref name) = result.host {
+                result.host = Host::Domain(name.to_lowercase());
+            }
+        }
+        if normalization.drop_default_port && (result.port == default_port(&result.scheme)) {
+            result.port = None;
+        }
+        if normalization.remove_dot_segments {
+            let path = remove_dot_segments(join_path(result.segments.clone(), result.trailing_slash));
+            result = result.with_path_segments(path, false);
+        }
+        return result;
+    }
+
+    pub fn equivalent(&self, other: &Plug) -> bool {
+        return self.normalize() == other.normalize();
+    }
+
+    pub fn starts_with(&self, prefix: &Plug) -> bool {
+        if !self.same_origin(prefix) {
+            return false;
+        }
+        match (self.opaque.as_ref(), prefix.opaque.as_ref()) {
+            (Some(opaque), Some(prefix_opaque)) => return opaque.starts_with(prefix_opaque.as_str()),
+            (None, None) => (),
+            _ => return false
+        }
+
+        // prefix with a trailing '/' only matches below itself
+        if !self.segments.starts_with(&prefix.segments) {
+            return false;
+        }
+        return !prefix.trailing_slash || (self.segments.len() > prefix.segments.len()) || self.trailing_slash;
+    }
+
+    pub fn relative_to(&self, base: &Plug) -> Option<String> {
+        if !self.same_origin(base) || self.opaque.is_some() || base.opaque.is_some() {
+            return None;
+        }
+        let mut buffer = String::new();
+        let target = join_path(self.segments.clone(), self.trailing_slash);
+        if let Some((last, target_directory)) = target.split_last() {
+
+            // walk up from the base directory to the common prefix, then down to the target
+            let base_path = &base.segments;
+            let base_directory = if base.trailing_slash {
+                &base_path[..]
+            } else {
+                &base_path[..base_path.len().saturating_sub(1)]
+            };
+            let common = base_directory.iter().zip(target_directory.iter()).take_while(|&(a, b)| a == b).count();
+            for _ in common..base_directory.len() {
+                buffer.push_str("../");
+            }
+            let rest: Vec<String> = target_directory[common..].iter().chain(Some(last)).map(encode_path_segment).collect();
+            let path = rest.join("/");
+
+            // './' keeps an empty path, a leading '/', or a ':' in the first segment from changing the meaning
+            if buffer.is_empty() && (path.is_empty() || path.starts_with('/') || rest[0].contains(':')) {
+                buffer.push_str("./");
+            }
+            buffer.push_str(&path);
+        } else if !base.segments.is_empty() || base.trailing_slash {
+
+            // empty path cannot be reached from a non-empty one
+            return None;
+        }
+        if self.query.is_none() && buffer.is_empty() && base.query.is_some() {
+            return None;
+        }
+        buffer.push_str(&self.encode_query());
+        if let Some(ref fragment) = self.fragment {
+            buffer.push('#');
+            buffer.push_str(&encode_fragment(fragment));
+        }
+        return Some(buffer);
+    }
+
+    pub fn path_and_query(&self) -> String {
+
+        // empty path is sent as '/' in a request line
+        let mut buffer = match self.opaque {
+            Some(ref opaque) => opaque.clone(),
+            None => self.encode_path()
+        };
+        if buffer.is_empty() {
+            buffer.push('/');
+        }
+        buffer.push_str(&self.encode_query());
+        return buffer;
+    }
+
+    fn write_uri<W: fmt::Write>(&self, f: &mut W, redacted: bool) -> fmt::Result {
+        f.write_str(&self.scheme)?;
+
+        // opaque path is stored encoded and replaces the authority and the path
+        if let Some(ref opaque) = self.opaque {
+            f.write_str(":")?;
+            f.write_str(opaque)?;
+        } else {
+            f.write_str("://")?;
+            self.write_authority(f, redacted)?;
+            f.write_str(&self.encode_path())?;
+        }
+        f.write_str(&self.encode_query())?;
+        if let Some(ref fragment) = self.fragment {
+            f.write_str("#")?;
+            f.write_str(&encode_fragment(fragment))?;
+        }
+        return Ok(());
+    }
+
+    fn same_origin(&self, other: &Plug) -> bool {
+        return self.scheme.eq_ignore_ascii_case(&other.scheme)
+            && (self.credentials == other.credentials)
+            && (self.host.to_string().eq_ignore_ascii_case(&other.host.to_string()))
+            && (self.effective_port() == other.effective_port());
+    }
+
+    fn write_authority<W: fmt::Write>(&self, f: &mut W, redacted: bool) -> fmt::Result {
+        match self.credentials  {
+            PlugCredentials::None => (),
+            PlugCredentials::Username(ref username) => {
+                f.write_str(&encode_userinfo(username))?;
+                f.write_str("@")?;
+            },
+            PlugCredentials::UsernamePassword(ref username, ref password) => {
+                f.write_str(&encode_userinfo(username))?;
+                f.write_str(":")?;
+                if redacted {
+                    f.write_str("***")?;
+                } else {
+                    f.write_str(&encode_userinfo(password.expose()))?;
+                }
+                f.write_str("@")?;
+            }
+        }
+        write!(f, "{}", self.host)?;
+
+        // port is kept as given, but the scheme's default port is not rendered
+        match self.port {
+            Some(port) if Some(port) != default_port(&self.scheme) => write!(f, ":{}", port)?,
+            _ => ()
+        }
+        return Ok(());
+    }
+
+    fn query_params<'a>(&'a self) -> slice::Iter<'a, (String, Option<String>)> {
+        return match self.query {
+            Some(ref params) => params.iter(),
+            None => [].iter()
+        };
+    }
+
+    fn checked(&self, plug: Plug) -> Plug {
+
+        // authority that would not parse back the same way under the scheme leaves the plug unchanged
+        if plug.opaque.is_some() {
+            return plug;
+        }
+        let valid = if is_unix_socket_scheme(&plug.scheme) {
+            match plug.host {
+                Host::UnixSocket(ref path) => !path.as_os_str().is_empty() && (plug.credentials == PlugCredentials::None) && plug.port.is_none(),
+                _ => false
+            }
+        } else {
+            match plug.host {
+                Host::UnixSocket(_) => false,
+                Host::Domain(ref name) if name.is_empty() => !requires_authority(&plug.scheme),
+
+                // domain name must pass the same checks as a parsed hostname
+                Host::Domain(ref name) => name.chars().all(|c| is_authority_char(c) && (c != '%')) && domain_to_ascii(name, 0).is_ok(),
+                _ => true
+            }
+        };
+        if valid {
+            return plug;
+        }
+        return self.clone();
+    }
+
+    fn with_path_segments(&self, segments: Vec<Segment>, trailing_slash: bool) -> Plug {
+        let (segments, trailing_slash) = canonical_path(segments, trailing_slash);
+        return Plug { segments: segments, trailing_slash: trailing_slash, ..self.clone() };
+    }
+
+    fn encode_path(&self) -> String {
+        let mut buffer = String::new();
+        for segment in self.segments.iter() {
+            buffer.push('/');
+            buffer.push_str(&encode_path_segment(segment));
+        }
+        if self.trailing_slash {
+            buffer.push('/');
+        }
+        return buffer;
+    }
+
+    fn encode_query(&self) -> String {
+        let mut buffer = String::new();
+        if let Some(ref query) = self.query {
+            buffer.push('?');
+            buffer.push_str(&form_urlencoded::serialize_pairs(query, Separator::Ampersand, false));
+        }
+        return buffer;
+    }
+
+    pub fn to_string_redacted(&self) -> String {
+
+        // password is replaced with '***'; use to_string for the full form
+        let mut buffer = String::new();
+        let _ = self.write_uri(&mut buffer, true);
+        return buffer;
+    }
+
     pub fn get_scheme(&self) -> &str {
         return &self.scheme;
     }
 
+    pub fn effective_port(&self) -> Option<u16> {
+        return self.port.or_else(|| default_port(&self.scheme));
+    }
+
     pub fn get_credentials(&self) -> &PlugCredentials {
         return &self.credentials;
     }
 
-    pub fn get_host(&self) -> &str {
+    pub fn get_host(&self) -> &Host {
         return &self.host;
     }
 
-    pub fn get_segments(&self) -> &[String] {
+    pub fn get_port(&self) -> Option<u16> {
+        return self.port;
+    }
+
+    pub fn host_unicode(&self) -> String {
+        return self.host.to_unicode();
+    }
+
+    pub fn host_ip(&self) -> Option<IpAddr> {
+        return self.host.get_ip();
+    }
+
+    pub fn get_segments(&self) -> impl Iterator<Item = &str> {
+        return self.segments.iter().map(|segment| segment.get_name());
+    }
+
+    pub fn get_path_segments(&self) -> &[Segment] {
         return &self.segments;
     }
 
@@ -132,47 +678,161 @@ impl Plug {
         return &self.query;
     }
 
+    pub fn has_param(&self, key: &str) -> bool {
+        return self.query_params().any(|(name, _)| name == key);
+    }
+
+    pub fn get_param(&self, key: &str) -> Option<&str> {
+
+        // first occurrence wins; flags have no value, use has_param to test for them
+        return self.query_params().find(|(name, _)| name == key).and_then(|(_, value)| value.as_deref());
+    }
+
+    pub fn get_params(&self, key: &str) -> Vec<&str> {
+        return self.query_params().filter(|(name, _)| name == key).filter_map(|(_, value)| value.as_deref()).collect();
+    }
+
+    pub fn param_as<T: FromStr>(&self, key: &str) -> Option<T> {
+        return self.get_param(key).and_then(|value| value.parse::<T>().ok());
+    }
+
     pub fn get_fragment(&self) -> &Option<String> {
         return &self.fragment;
     }
 
+    pub fn get_segment_params(&self, index: usize) -> Option<&[(String, Option<String>)]> {
+        return self.segments.get(index).map(|segment| segment.get_params());
+    }
+
+    pub fn get_segment_param(&self, index: usize, key: &str) -> Option<&str> {
+        return self.segments.get(index)?.get_param(key);
+    }
+
+    pub fn last_segment(&self) -> Option<&str> {
+        return self.segments.last().map(|segment| segment.get_name());
+    }
+
     pub fn get_trailing_slash(&self) -> bool {
         return self.trailing_slash;
     }
 
+    pub fn get_opaque_path(&self) -> Option<&str> {
+        return self.opaque.as_deref();
+    }
+
     pub fn with_scheme(&self, scheme: String) -> Plug {
-        return Plug { scheme: scheme, ..self.clone() };
+
+        // opaque path cannot be kept under a scheme that requires an authority
+        if self.opaque.is_some() && requires_authority(&scheme) {
+            return self.clone();
+        }
+        return self.checked(Plug { scheme: scheme, ..self.clone() });
     }
 
     pub fn with_credentials(&self, credentials: PlugCredentials) -> Plug {
-        return Plug { credentials: credentials, ..self.clone() };
+
+        // opaque plug has no authority, so authority and path builders leave it unchanged
+        if self.opaque.is_some() {
+            return self.clone();
+        }
+        return self.checked(Plug { credentials: credentials, ..self.clone() });
     }
 
     pub fn without_credentials(&self) -> Plug {
+        if self.opaque.is_some() {
+            return self.clone();
+        }
         return Plug { credentials: PlugCredentials::None, ..self.clone() };
     }
 
-    pub fn with_host(&self, host: String) -> Plug {
-        return Plug { host: host, ..self.clone() };
+    pub fn with_host(&self, host: Host) -> Plug {
+        if self.opaque.is_some() {
+            return self.clone();
+        }
+        return self.checked(Plug { host: canonical_host(host), ..self.clone() });
+    }
+
+    pub fn with_opaque_path(&self, path: &str) -> Plug {
+
+        // hierarchical plug becomes opaque, keeping only its scheme, query and fragment
+        if requires_authority(&self.scheme) {
+            return self.clone();
+        }
+        return Plug {
+            query: self.query.clone(),
+            fragment: self.fragment.clone(),
+            ..Plug::from_opaque_path(self.scheme.clone(), encode_canonical_opaque_path(path))
+        };
     }
 
     pub fn with_port(&self, port: u16) -> Plug {
-        return Plug { port: Some(port), ..self.clone() };
+        if self.opaque.is_some() {
+            return self.clone();
+        }
+        return self.checked(Plug { port: Some(port), ..self.clone() });
     }
 
     pub fn without_port(&self) -> Plug {
+        if self.opaque.is_some() {
+            return self.clone();
+        }
         return Plug { port: None, ..self.clone() };
     }
 
     pub fn at(&self, segments: Vec<String>) -> Plug {
-        let mut new_segments = self.segments.to_vec();
-        for segment in segments.into_iter() {
-            new_segments.push(segment);
+        if self.opaque.is_some() {
+            return self.clone();
         }
-        return Plug { segments: new_segments, ..self.clone() };
+        let mut new_segments = self.segments.clone();
+        new_segments.extend(segments.into_iter().map(Segment::new));
+        return self.with_path_segments(new_segments, self.trailing_slash);
+    }
+
+    pub fn at_path(&self, path: &str) -> Plug {
+
+        // path is split at '/' and each segment is encoded when rendered; a trailing '/' is kept
+        let path = path.strip_prefix('/').unwrap_or(path);
+        if path.is_empty() {
+            return self.clone();
+        }
+        return self.without_trailing_slash().at(path.split('/').map(String::from).collect());
+    }
+
+    pub fn with_segment(&self, index: usize, segment: String) -> Option<Plug> {
+        if index >= self.segments.len() {
+            return None;
+        }
+
+        // replaced segment drops its matrix parameters
+        let mut new_segments = self.segments.clone();
+        new_segments[index] = Segment::new(segment);
+        return Some(self.with_path_segments(new_segments, self.trailing_slash));
+    }
+
+    pub fn with_segment_param(&self, index: usize, key: String, value: String) -> Option<Plug> {
+        let mut new_segments = self.segments.clone();
+        let segment = new_segments.get_mut(index)?;
+        *segment = segment.with_param(key, value);
+        return Some(Plug { segments: new_segments, ..self.clone() });
+    }
+
+    pub fn with_segment_flag(&self, index: usize, key: String) -> Option<Plug> {
+        let mut new_segments = self.segments.clone();
+        let segment = new_segments.get_mut(index)?;
+        *segment = segment.with_flag(key);
+        return Some(Plug { segments: new_segments, ..self.clone() });
+    }
+
+    pub fn parent(&self) -> Option<Plug> {
+        let mut new_segments = self.segments.clone();
+        new_segments.pop()?;
+        return Some(self.with_path_segments(new_segments, self.trailing_slash));
     }
 
     pub fn without_path(&self) -> Plug {
+        if self.opaque.is_some() {
+            return self.clone();
+        }
         return Plug { segments: Vec::new(), ..self.clone() };
     }
 
@@ -181,10 +841,76 @@ impl Plug {
             Some(ref params) => params.to_vec(),
             None => Vec::new(),
         };
-        new_query.push((key.into(), Some(value.into())));
+        new_query.push((key, Some(value)));
+        return Plug { query: Some(new_query), ..self.clone() };
+    }
+
+    pub fn with_flag(&self, key: String) -> Plug {
+        let mut new_query = self.query.clone().unwrap_or_default();
+        new_query.push((key, None));
+        return Plug { query: Some(canonical_query(new_query)), ..self.clone() };
+    }
+
+    pub fn with_params<I: IntoIterator<Item = (String, String)>>(&self, params: I) -> Plug {
+        let mut new_query = self.query.clone().unwrap_or_default();
+        new_query.extend(params.into_iter().map(|(key, value)| (key, Some(value))));
+        return Plug { query: Some(new_query), ..self.clone() };
+    }
+
+    pub fn without_param(&self, key: &str) -> Plug {
+        let new_query: Vec<_> = self.query_params().filter(|(name, _)| name != key).cloned().collect();
+
+        // query is left as is when nothing is removed; removing the last parameter removes the query altogether
+        if new_query.len() == self.query_params().count() {
+            return self.clone();
+        }
+        if new_query.is_empty() {
+            return self.without_query();
+        }
+        return Plug { query: Some(new_query), ..self.clone() };
+    }
+
+    pub fn replace_param(&self, key: String, value: String) -> Plug {
+
+        // value takes the place of the first occurrence; any others are dropped
+        let mut new_query = Vec::new();
+        let mut replaced = false;
+        for (name, old_value) in self.query_params() {
+            if *name != key {
+                new_query.push((name.clone(), old_value.clone()));
+            } else if !replaced {
+                new_query.push((key.clone(), Some(value.clone())));
+                replaced = true;
+            }
+        }
+        if !replaced {
+            new_query.push((key, Some(value)));
+        }
         return Plug { query: Some(new_query), ..self.clone() };
     }
 
+    #[cfg(feature = "serde")]
+    pub fn with_query_struct<T: Serialize>(&self, value: &T) -> Result<Plug, serde_urlencoded::ser::Error> {
+
+        // struct fields are appended in declaration order after any existing parameters
+        let text = serde_urlencoded::to_string(value)?;
+        let params = form_urlencoded::parse(&text).map_err(|error| serde_urlencoded::ser::Error::Custom(error.to_string().into()))?;
+        let mut new_query = self.query.clone().unwrap_or_default();
+        new_query.extend(params);
+        return Ok(Plug { query: Some(new_query), ..self.clone() });
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn query_as<T: DeserializeOwned>(&self) -> Result<T, serde_urlencoded::de::Error> {
+
+        // flags are seen as parameters with an empty value
+        let text = match self.query {
+            Some(ref query) => form_urlencoded::serialize(query),
+            None => String::new()
+        };
+        return serde_urlencoded::from_str(&text);
+    }
+
     pub fn without_query(&self) -> Plug {
         return Plug { query: None, ..self.clone() };
     }
@@ -198,57 +924,442 @@ impl Plug {
     }
 
     pub fn with_trailing_slash(&self, trailing_slash: bool) -> Plug {
-        return Plug { trailing_slash: trailing_slash, ..self.clone() };
+        if self.opaque.is_some() {
+            return self.clone();
+        }
+        return self.with_path_segments(self.segments.clone(), trailing_slash);
     }
 
     pub fn without_trailing_slash(&self) -> Plug {
-        return Plug { trailing_slash: false, ..self.clone() };
+        return self.with_trailing_slash(false);
     }
 }
 
-impl ToString for Plug {
-    fn to_string(&self) -> String {
-        let mut buffer = String::new();
-        buffer.push_str(&self.scheme);
-        buffer.push_str("://");
-        match self.credentials  {
-            PlugCredentials::None => (),
-            PlugCredentials::Username(ref username) => {
-                buffer.push_str(&username);
-                buffer.push_str("@");
-            },
-            PlugCredentials::UsernamePassword(ref username, ref password) => {
-                buffer.push_str(&username);
-                buffer.push_str(":");
-                buffer.push_str(&password);
-                buffer.push_str("@");
-            }
+impl PlugNormalization {
+    pub fn all() -> PlugNormalization {
+        return PlugNormalization {
+            lowercase_scheme_and_host: true,
+            drop_default_port: true,
+            remove_dot_segments: true,
+            uppercase_escapes: true,
+            decode_unreserved: true
+        };
+    }
+
+    pub fn none() -> PlugNormalization {
+        return PlugNormalization {
+            lowercase_scheme_and_host: false,
+            drop_default_port: false,
+            remove_dot_segments: false,
+            uppercase_escapes: false,
+            decode_unreserved: false
+        };
+    }
+
+    pub fn get_lowercase_scheme_and_host(&self) -> bool {
+        return self.lowercase_scheme_and_host;
+    }
+
+    pub fn get_drop_default_port(&self) -> bool {
+        return self.drop_default_port;
+    }
+
+    pub fn get_remove_dot_segments(&self) -> bool {
+        return self.remove_dot_segments;
+    }
+
+    pub fn get_uppercase_escapes(&self) -> bool {
+        return self.uppercase_escapes;
+    }
+
+    pub fn get_decode_unreserved(&self) -> bool {
+        return self.decode_unreserved;
+    }
+
+    pub fn with_lowercase_scheme_and_host(&self, enabled: bool) -> PlugNormalization {
+        return PlugNormalization { lowercase_scheme_and_host: enabled, ..*self };
+    }
+
+    pub fn with_drop_default_port(&self, enabled: bool) -> PlugNormalization {
+        return PlugNormalization { drop_default_port: enabled, ..*self };
+    }
+
+    pub fn with_remove_dot_segments(&self, enabled: bool) -> PlugNormalization {
+        return PlugNormalization { remove_dot_segments: enabled, ..*self };
+    }
+
+    pub fn with_uppercase_escapes(&self, enabled: bool) -> PlugNormalization {
+        return PlugNormalization { uppercase_escapes: enabled, ..*self };
+    }
+
+    pub fn with_decode_unreserved(&self, enabled: bool) -> PlugNormalization {
+        return PlugNormalization { decode_unreserved: enabled, ..*self };
+    }
+}
+
+impl Default for PlugNormalization {
+    fn default() -> PlugNormalization {
+        return PlugNormalization::all();
+    }
+}
+
+impl From<UriCredentials> for PlugCredentials {
+    fn from(credentials: UriCredentials) -> PlugCredentials {
+        match credentials {
+            UriCredentials::None => return PlugCredentials::None,
+            UriCredentials::Username(username) => return PlugCredentials::Username(username),
+            UriCredentials::UsernamePassword(username, password) => return PlugCredentials::UsernamePassword(username, Secret::new(password))
         }
-        buffer.push_str(&self.host);
-        if let Some(port) = self.port {
-            buffer.push_str(&format!(":{}", port));
+    }
+}
+
+impl PlugParserError {
+    pub fn get_kind(&self) -> UriErrorKind {
+        return self.kind;
+    }
+
+    pub fn get_component(&self) -> UriComponent {
+        return self.component;
+    }
+
+    pub fn get_offset(&self) -> usize {
+        return self.offset;
+    }
+
+    pub fn get_found(&self) -> Option<char> {
+        return self.found;
+    }
+}
+
+impl From<UriParserError> for PlugParserError {
+    fn from(error: UriParserError) -> PlugParserError {
+        return PlugParserError {
+            kind: error.kind,
+            component: error.component,
+            offset: error.offset,
+            found: error.found
+        };
+    }
+}
+
+impl fmt::Display for PlugParserError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write_error(f, self.kind, self.component, self.offset, self.found);
+    }
+}
+
+impl Error for PlugParserError {}
+
+impl fmt::Display for PlugFilePathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return f.write_str(match *self {
+            PlugFilePathError::RelativePath => "path is not absolute",
+            PlugFilePathError::NonUnicodePath => "path is not valid Unicode",
+            PlugFilePathError::InvalidScheme => "scheme is not 'file'",
+            PlugFilePathError::InvalidHost => "host is not local",
+            PlugFilePathError::InvalidSegment => "segment contains a path separator"
+        });
+    }
+}
+
+impl Error for PlugFilePathError {}
+
+impl PartialEq for Plug {
+    fn eq(&self, other: &Plug) -> bool {
+
+        // default port is not rendered, so it is equal to no port at all
+        return (self.scheme == other.scheme)
+            && (self.credentials == other.credentials)
+            && (self.host == other.host)
+            && (self.effective_port() == other.effective_port())
+            && (self.segments == other.segments)
+            && (self.query == other.query)
+            && (self.fragment == other.fragment)
+            && (self.trailing_slash == other.trailing_slash)
+            && (self.opaque == other.opaque);
+    }
+}
+
+impl fmt::Display for Plug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return self.write_uri(f, false);
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Plug {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.collect_str(self);
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Plug {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Plug, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        return Plug::parse(&text).map_err(de::Error::custom);
+    }
+}
+
+impl ToSocketAddrs for Plug {
+    type Iter = vec::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<vec::IntoIter<SocketAddr>> {
+
+        // port falls back to the scheme's default port
+        let port = match self.effective_port() {
+            Some(port) => port,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "missing port number"))
+        };
+        match self.host {
+            Host::Ipv4(address) => return Ok(vec![SocketAddr::new(IpAddr::V4(address), port)].into_iter()),
+            Host::Ipv6(address, None) => return Ok(vec![SocketAddr::new(IpAddr::V6(address), port)].into_iter()),
+            Host::Ipv6(address, Some(ref zone_id)) => {
+
+                // only numeric zone ids can be mapped to a scope id
+                match zone_id.parse::<u32>() {
+                    Ok(scope_id) => return Ok(vec![SocketAddr::V6(SocketAddrV6::new(address, port, 0, scope_id))].into_iter()),
+                    Err(_) => return Err(io::Error::new(io::ErrorKind::InvalidInput, "zone id is not numeric"))
+                }
+            },
+            Host::Domain(ref name) if !name.is_empty() => return (name.as_str(), port).to_socket_addrs(),
+            Host::Domain(_) => return Err(io::Error::new(io::ErrorKind::InvalidInput, "missing host")),
+            Host::UnixSocket(_) => return Err(io::Error::new(io::ErrorKind::InvalidInput, "host is a unix socket"))
         }
-        for segment in self.segments.iter() {
-            buffer.push_str("/");
-            buffer.push_str(&segment);
+    }
+}
+
+#[cfg(feature = "url")]
+impl<'a> TryFrom<&'a url::Url> for Plug {
+    type Error = PlugParserError;
+
+    fn try_from(url: &'a url::Url) -> Result<Plug, PlugParserError> {
+
+        // url keeps the user-info encoded, so parsing it restores the username and password as given
+        return Plug::parse(url.as_str());
+    }
+}
+
+#[cfg(feature = "url")]
+impl TryFrom<url::Url> for Plug {
+    type Error = PlugParserError;
+
+    fn try_from(url: url::Url) -> Result<Plug, PlugParserError> {
+        return Plug::try_from(&url);
+    }
+}
+
+#[cfg(feature = "url")]
+impl<'a> TryFrom<&'a Plug> for url::Url {
+    type Error = url::ParseError;
+
+    fn try_from(plug: &'a Plug) -> Result<url::Url, url::ParseError> {
+
+        // url cannot hold an empty password, so 'bob:@' becomes 'bob@' and converts back as a username only
+        return url::Url::parse(&plug.to_string());
+    }
+}
+
+#[cfg(feature = "url")]
+impl TryFrom<Plug> for url::Url {
+    type Error = url::ParseError;
+
+    fn try_from(plug: Plug) -> Result<url::Url, url::ParseError> {
+        return url::Url::try_from(&plug);
+    }
+}
+
+#[cfg(feature = "http")]
+impl<'a> TryFrom<&'a http::Uri> for Plug {
+    type Error = PlugParserError;
+
+    fn try_from(uri: &'a http::Uri) -> Result<Plug, PlugParserError> {
+        return Plug::parse(&uri.to_string());
+    }
+}
+
+#[cfg(feature = "http")]
+impl TryFrom<http::Uri> for Plug {
+    type Error = PlugParserError;
+
+    fn try_from(uri: http::Uri) -> Result<Plug, PlugParserError> {
+        return Plug::try_from(&uri);
+    }
+}
+
+#[cfg(feature = "http")]
+impl<'a> TryFrom<&'a Plug> for http::Uri {
+    type Error = http::uri::InvalidUri;
+
+    fn try_from(plug: &'a Plug) -> Result<http::Uri, http::uri::InvalidUri> {
+
+        // fragment is never sent to the server, so http::Uri has no place for it
+        return http::Uri::try_from(plug.without_fragment().to_string());
+    }
+}
+
+#[cfg(feature = "http")]
+impl TryFrom<Plug> for http::Uri {
+    type Error = http::uri::InvalidUri;
+
+    fn try_from(plug: Plug) -> Result<http::Uri, http::uri::InvalidUri> {
+        return http::Uri::try_from(&plug);
+    }
+}
+
+#[cfg(feature = "http")]
+impl<'a> TryFrom<&'a Plug> for http::uri::Authority {
+    type Error = http::uri::InvalidUri;
+
+    fn try_from(plug: &'a Plug) -> Result<http::uri::Authority, http::uri::InvalidUri> {
+        let mut buffer = String::new();
+        let _ = plug.write_authority(&mut buffer, false);
+        return http::uri::Authority::try_from(buffer);
+    }
+}
+
+#[cfg(feature = "http")]
+impl<'a> TryFrom<&'a Plug> for http::uri::PathAndQuery {
+    type Error = http::uri::InvalidUri;
+
+    fn try_from(plug: &'a Plug) -> Result<http::uri::PathAndQuery, http::uri::InvalidUri> {
+        return http::uri::PathAndQuery::try_from(plug.path_and_query());
+    }
+}
+
+impl FromStr for Plug {
+    type Err = PlugParserError;
+
+    fn from_str(uri: &str) -> Result<Plug, PlugParserError> {
+        return Plug::parse(uri);
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Plug {
+    type Error = PlugParserError;
+
+    fn try_from(uri: &'a str) -> Result<Plug, PlugParserError> {
+        return Plug::parse(uri);
+    }
+}
+
+impl TryFrom<String> for Plug {
+    type Error = PlugParserError;
+
+    fn try_from(uri: String) -> Result<Plug, PlugParserError> {
+        return Plug::parse(&uri);
+    }
+}
+
+impl From<Plug> for String {
+    fn from(plug: Plug) -> String {
+        return plug.to_string();
+    }
+}
+
+impl<'a> From<&'a Plug> for String {
+    fn from(plug: &'a Plug) -> String {
+        return plug.to_string();
+    }
+}
+
+fn join_path(mut segments: Vec<Segment>, trailing_slash: bool) -> Vec<Segment> {
+
+    // trailing '/' is represented by an empty last segment
+    if trailing_slash {
+        segments.push(Segment::new(String::new()));
+    }
+    return segments;
+}
+
+fn split_path(mut segments: Vec<Segment>) -> (Vec<Segment>, bool) {
+
+    // segment with only matrix parameters is not empty
+    if segments.last().is_some_and(Segment::is_empty) {
+        segments.pop();
+        return (segments, true);
+    }
+    return (segments, false);
+}
+
+fn canonical_path(segments: Vec<Segment>, trailing_slash: bool) -> (Vec<Segment>, bool) {
+
+    // empty last segment renders the same as a trailing '/'
+    return split_path(join_path(segments, trailing_slash));
+}
+
+fn encode_path_segment(segment: &Segment) -> String {
+    let mut buffer = encode_segment_name(segment.get_name());
+    for (key, value) in segment.get_params().iter() {
+        buffer.push(';');
+        buffer.push_str(&encode_segment_param_key(key));
+        if let Some(ref value) = *value {
+            buffer.push('=');
+            buffer.push_str(&encode_segment_param_value(value));
         }
-        if self.trailing_slash {
-            buffer.push_str("/");
+    }
+    return buffer;
+}
+
+fn segment_text(segment: &Segment) -> String {
+
+    // file paths have no matrix parameters, so they are kept as part of the name
+    let mut buffer = String::from(segment.get_name());
+    for (key, value) in segment.get_params().iter() {
+        buffer.push(';');
+        buffer.push_str(key);
+        if let Some(ref value) = *value {
+            buffer.push('=');
+            buffer.push_str(value);
         }
-        if let Some(ref query) = self.query {
-            buffer.push_str("?");
-            for &(ref key, ref optional_value) in query.iter() {
-                buffer.push_str(&key);
-                if let &Some(ref value) = optional_value {
-                    buffer.push_str("=");
-                    buffer.push_str(&value);
-                }
+    }
+    return buffer;
+}
+
+fn canonical_host(host: Host) -> Host {
+    match host {
+
+        // domain names are stored as parsed: IPv4 literals as addresses and international names as punycode
+        Host::Domain(name) => {
+            if let Ok(address) = name.parse() {
+                return Host::Ipv4(address);
             }
-        }
-        if let Some(ref fragment) = self.fragment {
-            buffer.push_str("#");
-            buffer.push_str(fragment);
-        }
-        return buffer;
+            match domain_to_ascii(&name, 0) {
+                Ok(Some(ascii)) => return Host::Domain(ascii),
+                _ => return Host::Domain(name)
+            }
+        },
+        host => return host
     }
 }
+
+fn canonical_query(query: Vec<(String, Option<String>)>) -> Vec<(String, Option<String>)> {
+
+    // single parameter with neither key nor value renders the same as an empty query
+    if (query.len() == 1) && query[0].0.is_empty() && query[0].1.is_none() {
+        return Vec::new();
+    }
+    return query;
+}
+
+fn encode_canonical_opaque_path(path: &str) -> String {
+
+    // leading '/' would make the path hierarchical
+    match path.strip_prefix('/') {
+        Some(rest) => return format!("%2F{}", encode_opaque_path(rest)),
+        None => return encode_opaque_path(path)
+    }
+}
+
+fn is_drive_letter(segment: &str) -> bool {
+    let bytes = segment.as_bytes();
+    return (bytes.len() == 2) && bytes[0].is_ascii_alphabetic() && (bytes[1] == b':');
+}
+
+fn default_port(scheme: &str) -> Option<u16> {
+    return scheme::lookup(scheme).and_then(|scheme| scheme.get_default_port());
+}
+
+fn requires_authority(scheme: &str) -> bool {
+    return scheme::lookup(scheme).is_some_and(|scheme| scheme.get_authority_required());
+}
@@ -0,0 +1,374 @@
+/*
+ * RustyPlug - a rust module with a fluid interface for building requests to sockets
+ *
+ * Copyright (C) 2016 Steve G. Bjorg
+ *
+ * For community documentation and downloads visit mindtouch.com;
+ * please review the licensing section.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use encoding::decode;
+use plug::Plug;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlugPattern {
+    text: String,
+    segments: Vec<PatternSegment>,
+    query: Vec<QueryConstraint>
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Params {
+    values: Vec<(String, String)>
+}
+
+#[derive(Clone, Debug)]
+pub struct Router<T> {
+    routes: Vec<(PlugPattern, T)>
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CaptureType {
+    Str,
+    Int,
+    Uint,
+    Float,
+    Bool
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlugPatternError {
+    InvalidPath(usize),
+    InvalidCapture(usize),
+    InvalidCaptureType(usize),
+    MisplacedWildcard(usize),
+    InvalidQuery(usize)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum PatternSegment {
+    Literal(String),
+    Capture(String, CaptureType),
+    Wildcard(String)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum QueryConstraint {
+    Present(String),
+    Equals(String, String),
+    Capture(String, String, CaptureType)
+}
+
+impl PlugPattern {
+    pub fn parse(pattern: &str) -> Result<PlugPattern, PlugPatternError> {
+
+        // pattern must begin with '/'; the query constraints follow '?'
+        if !pattern.starts_with('/') {
+            return Err(PlugPatternError::InvalidPath(0));
+        }
+        let (path, query) = match pattern.find('?') {
+            Some(index) => (&pattern[..index], Some((&pattern[(index + 1)..], index + 1))),
+            None => (pattern, None)
+        };
+
+        // trailing '/' does not start another segment
+        let path = if (path.len() > 1) && path.ends_with('/') {
+            &path[..(path.len() - 1)]
+        } else {
+            path
+        };
+        let mut segments = Vec::new();
+        let mut offset = 1;
+        if path.len() > 1 {
+            for segment in path[1..].split('/') {
+                if let Some(&PatternSegment::Wildcard(_)) = segments.last() {
+                    return Err(PlugPatternError::MisplacedWildcard(offset));
+                }
+                segments.push(parse_segment(segment, offset)?);
+                offset += segment.len() + 1;
+            }
+        }
+        let mut constraints = Vec::new();
+        if let Some((query, mut offset)) = query {
+            for param in query.split('&') {
+                constraints.push(parse_query_constraint(param, offset)?);
+                offset += param.len() + 1;
+            }
+        }
+        return Ok(PlugPattern {
+            text: pattern.into(),
+            segments: segments,
+            query: constraints
+        });
+    }
+
+    pub fn as_str(&self) -> &str {
+        return &self.text;
+    }
+
+    pub fn matches(&self, plug: &Plug) -> Option<Params> {
+        let mut params = Params::default();
+        let segments: Vec<&str> = plug.get_segments().collect();
+        let mut index = 0;
+        for segment in self.segments.iter() {
+            match *segment {
+                PatternSegment::Literal(ref literal) => {
+                    if segments.get(index) != Some(&literal.as_str()) {
+                        return None;
+                    }
+                },
+                PatternSegment::Capture(ref name, capture_type) => {
+                    match segments.get(index) {
+                        Some(value) if capture_type.accepts(value) => params.values.push((name.clone(), String::from(*value))),
+                        _ => return None
+                    }
+                },
+                PatternSegment::Wildcard(ref name) => {
+
+                    // wildcard captures the remaining segments, if any
+                    params.values.push((name.clone(), segments[index..].join("/")));
+                    index = segments.len();
+                    continue;
+                }
+            }
+            index += 1;
+        }
+        if index != segments.len() {
+            return None;
+        }
+
+        // every query constraint must be met by at least one parameter
+        let empty = Vec::new();
+        let query = plug.get_query().as_ref().unwrap_or(&empty);
+        for constraint in self.query.iter() {
+            match *constraint {
+                QueryConstraint::Present(ref key) => {
+                    if !query.iter().any(|(name, _)| name == key) {
+                        return None;
+                    }
+                },
+                QueryConstraint::Equals(ref key, ref expected) => {
+                    if !query.iter().any(|(name, value)| (name == key) && (value.as_ref() == Some(expected))) {
+                        return None;
+                    }
+                },
+                QueryConstraint::Capture(ref key, ref capture, capture_type) => {
+                    match query.iter().find(|&(name, _)| name == key) {
+                        Some((_, Some(value))) if capture_type.accepts(value) => params.values.push((capture.clone(), value.clone())),
+                        _ => return None
+                    }
+                }
+            }
+        }
+        return Some(params);
+    }
+
+    fn get_specificity(&self) -> (Vec<u8>, usize) {
+
+        // literals beat typed captures, which beat untyped captures, which beat wildcards;
+        // the end of the pattern beats a wildcard, so an exact-length match wins over one that matched nothing
+        let mut ranks: Vec<u8> = self.segments.iter().map(|segment| {
+            match *segment {
+                PatternSegment::Literal(_) => return 5,
+                PatternSegment::Capture(_, CaptureType::Str) => return 3,
+                PatternSegment::Capture(_, _) => return 4,
+                PatternSegment::Wildcard(_) => return 1
+            }
+        }).collect();
+        ranks.push(2);
+        return (ranks, self.query.len());
+    }
+}
+
+impl Params {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        return self.values.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+    }
+
+    pub fn get_as<T: FromStr>(&self, name: &str) -> Option<T> {
+        return self.get(name).and_then(|value| value.parse::<T>().ok());
+    }
+
+    pub fn get_all(&self) -> &[(String, String)] {
+        return &self.values;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.values.is_empty();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.values.len();
+    }
+}
+
+impl<T> Router<T> {
+    pub fn new() -> Router<T> {
+        return Router {
+            routes: Vec::new()
+        };
+    }
+
+    pub fn add(&mut self, pattern: PlugPattern, value: T) {
+        self.routes.push((pattern, value));
+    }
+
+    pub fn route(&self, plug: &Plug) -> Option<(&T, Params)> {
+
+        // most specific match wins; among equally specific ones, the first added wins
+        let mut best: Option<(&PlugPattern, &T, Params)> = None;
+        for (pattern, value) in self.routes.iter() {
+            if let Some(params) = pattern.matches(plug) {
+                let better = match best {
+                    Some((current, _, _)) => pattern.get_specificity() > current.get_specificity(),
+                    None => true
+                };
+                if better {
+                    best = Some((pattern, value, params));
+                }
+            }
+        }
+        return best.map(|(_, value, params)| (value, params));
+    }
+}
+
+impl<T> Default for Router<T> {
+    fn default() -> Router<T> {
+        return Router::new();
+    }
+}
+
+impl CaptureType {
+    fn accepts(self, value: &str) -> bool {
+        match self {
+            CaptureType::Str => return !value.is_empty(),
+            CaptureType::Int => return value.parse::<i64>().is_ok(),
+            CaptureType::Uint => return value.parse::<u64>().is_ok(),
+            CaptureType::Float => return value.parse::<f64>().is_ok(),
+            CaptureType::Bool => return (value == "true") || (value == "false")
+        }
+    }
+}
+
+impl FromStr for PlugPattern {
+    type Err = PlugPatternError;
+
+    fn from_str(pattern: &str) -> Result<PlugPattern, PlugPatternError> {
+        return PlugPattern::parse(pattern);
+    }
+}
+
+impl fmt::Display for PlugPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return f.write_str(&self.text);
+    }
+}
+
+impl fmt::Display for PlugPatternError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PlugPatternError::InvalidPath(offset) => return write!(f, "invalid path at offset {}", offset),
+            PlugPatternError::InvalidCapture(offset) => return write!(f, "invalid capture at offset {}", offset),
+            PlugPatternError::InvalidCaptureType(offset) => return write!(f, "invalid capture type at offset {}", offset),
+            PlugPatternError::MisplacedWildcard(offset) => return write!(f, "wildcard must be the last segment at offset {}", offset),
+            PlugPatternError::InvalidQuery(offset) => return write!(f, "invalid query constraint at offset {}", offset)
+        }
+    }
+}
+
+impl Error for PlugPatternError {}
+
+fn parse_segment(segment: &str, offset: usize) -> Result<PatternSegment, PlugPatternError> {
+    if !segment.starts_with('{') {
+
+        // literal segment is compared against the decoded plug segment
+        if segment.contains('{') || segment.contains('}') {
+            return Err(PlugPatternError::InvalidCapture(offset));
+        }
+        return decode(segment).map(PatternSegment::Literal).map_err(|_| PlugPatternError::InvalidPath(offset));
+    }
+    if !segment.ends_with('}') || (segment.len() < 3) {
+        return Err(PlugPatternError::InvalidCapture(offset));
+    }
+    let capture = &segment[1..(segment.len() - 1)];
+    if let Some(name) = capture.strip_prefix('*') {
+        if !is_name(name) {
+            return Err(PlugPatternError::InvalidCapture(offset + 2));
+        }
+        return Ok(PatternSegment::Wildcard(name.into()));
+    }
+    let (name, capture_type) = parse_capture(capture, offset + 1)?;
+    return Ok(PatternSegment::Capture(name, capture_type));
+}
+
+fn parse_query_constraint(param: &str, offset: usize) -> Result<QueryConstraint, PlugPatternError> {
+    let (key, value) = match param.find('=') {
+        Some(index) => (&param[..index], Some(&param[(index + 1)..])),
+        None => (param, None)
+    };
+    let key = decode(key).map_err(|_| PlugPatternError::InvalidQuery(offset))?;
+    if key.is_empty() {
+        return Err(PlugPatternError::InvalidQuery(offset));
+    }
+    match value {
+        None => return Ok(QueryConstraint::Present(key)),
+
+        // value in braces captures the parameter value
+        Some(value) if value.starts_with('{') => {
+            let value_offset = offset + param.len() - value.len();
+            if !value.ends_with('}') || (value.len() < 3) {
+                return Err(PlugPatternError::InvalidCapture(value_offset));
+            }
+            let (name, capture_type) = parse_capture(&value[1..(value.len() - 1)], value_offset + 1)?;
+            return Ok(QueryConstraint::Capture(key, name, capture_type));
+        },
+        Some(value) => {
+            let value = decode(value).map_err(|_| PlugPatternError::InvalidQuery(offset))?;
+            return Ok(QueryConstraint::Equals(key, value));
+        }
+    }
+}
+
+fn parse_capture(capture: &str, offset: usize) -> Result<(String, CaptureType), PlugPatternError> {
+
+    // capture is 'name' or 'name:type'
+    let (name, capture_type) = match capture.find(':') {
+        Some(index) => {
+            let capture_type = match &capture[(index + 1)..] {
+                "str" => CaptureType::Str,
+                "int" => CaptureType::Int,
+                "uint" => CaptureType::Uint,
+                "float" => CaptureType::Float,
+                "bool" => CaptureType::Bool,
+                _ => return Err(PlugPatternError::InvalidCaptureType(offset + index + 1))
+            };
+            (&capture[..index], capture_type)
+        },
+        None => (capture, CaptureType::Str)
+    };
+    if !is_name(name) {
+        return Err(PlugPatternError::InvalidCapture(offset));
+    }
+    return Ok((name.into(), capture_type));
+}
+
+fn is_name(name: &str) -> bool {
+    return !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || (b == b'_'));
+}
@@ -0,0 +1,445 @@
+/*
+ * RustyPlug - a rust module with a fluid interface for building requests to sockets
+ *
+ * Copyright (C) 2016 Steve G. Bjorg
+ *
+ * For community documentation and downloads visit mindtouch.com;
+ * please review the licensing section.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::Ipv6Addr;
+use plug::{Plug, PlugParserError};
+use scheme;
+use uri_parser::{domain_to_ascii, is_authority_char, is_unix_socket_scheme, is_path_char, UriComponent, UriErrorKind, UriParserError};
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct PlugRef<'a> {
+    text: &'a str,
+    scheme: &'a str,
+    username: Option<&'a str>,
+    password: Option<&'a str>,
+    host: &'a str,
+    port: Option<u16>,
+    path: &'a str,
+    query: Option<&'a str>,
+    fragment: Option<&'a str>,
+    opaque: bool
+}
+
+impl<'a> PlugRef<'a> {
+    pub fn parse(text: &'a str) -> Result<PlugRef<'a>, PlugParserError> {
+        let bytes = text.as_bytes();
+
+        // scheme must begin with alphabetic character
+        match bytes.first() {
+            Some(b) if b.is_ascii_alphabetic() => (),
+            _ => return Err(error(text, 0, UriErrorKind::InvalidScheme, UriComponent::Scheme))
+        }
+        let scheme_end = find(bytes, 1, bytes.len(), |b| !b.is_ascii_alphanumeric() && (b != b'+') && (b != b'-') && (b != b'.'));
+        match bytes.get(scheme_end) {
+            Some(&b':') | None => (),
+            _ => return Err(error(text, scheme_end, UriErrorKind::InvalidScheme, UriComponent::Scheme))
+        }
+
+        // scheme followed by ':', but not by '//' or '/', has an opaque path, which may be empty;
+        // schemes that require an authority never do
+        let authority_required = scheme::lookup(&text[..scheme_end]).is_some_and(|scheme| scheme.get_authority_required());
+        if (Some(&b':') == bytes.get(scheme_end)) && !matches!(bytes.get(scheme_end + 1), Some(&b'/') | Some(&b'\\')) && !authority_required {
+            let path_start = scheme_end + 1;
+            let path_end = find(bytes, path_start, bytes.len(), |b| (b == b'?') || (b == b'#'));
+            validate(text, path_start, path_end, |c| is_path_char(c) || (c == '/'), true, UriErrorKind::InvalidPath, UriComponent::Path)?;
+            let (query, fragment) = split_query_and_fragment(text, path_end)?;
+            return Ok(PlugRef {
+                text: text,
+                scheme: &text[..scheme_end],
+                username: None,
+                password: None,
+                host: &text[path_start..path_start],
+                port: None,
+                path: &text[path_start..path_end],
+                query: query,
+                fragment: fragment,
+                opaque: true
+            });
+        }
+
+        // scheme must be followed by '://'
+        let separator = b"://";
+        let matched = bytes[scheme_end..].iter().zip(separator.iter()).take_while(|&(a, b)| a == b).count();
+        if matched < separator.len() {
+            return Err(error(text, scheme_end + matched, UriErrorKind::MissingColonSlashSlash, UriComponent::Scheme));
+        }
+
+        // authority ends at the first '/', '\', '?' or '#'
+        let authority_start = scheme_end + separator.len();
+        let authority_end = find(bytes, authority_start, bytes.len(), |b| (b == b'/') || (b == b'\\') || (b == b'?') || (b == b'#'));
+
+        // socket path takes the place of the host and the port
+        if is_unix_socket_scheme(&text[..scheme_end]) {
+            if authority_start == authority_end {
+                return Err(error(text, authority_end, UriErrorKind::InvalidHostname, UriComponent::Host));
+            }
+            validate(text, authority_start, authority_end, is_authority_char, true, UriErrorKind::InvalidHostname, UriComponent::Host)?;
+            validate_utf8(text, authority_start, authority_end, |_| false, UriComponent::Host)?;
+            let path_end = find(bytes, authority_end, bytes.len(), |b| (b == b'?') || (b == b'#'));
+            validate(text, authority_end, path_end, |c| is_path_char(c) || (c == '/') || (c == '\\'), true, UriErrorKind::InvalidPath, UriComponent::Path)?;
+            validate_utf8(text, authority_end, path_end, is_path_separator, UriComponent::Path)?;
+            let (query, fragment) = split_query_and_fragment(text, path_end)?;
+            return Ok(PlugRef {
+                text: text,
+                scheme: &text[..scheme_end],
+                username: None,
+                password: None,
+                host: &text[authority_start..authority_end],
+                port: None,
+                path: &text[authority_end..path_end],
+                query: query,
+                fragment: fragment,
+                opaque: false
+            });
+        }
+
+        // user-info ends at '@'; part before ':' is the username, part after is the password
+        let (username, password, host_start) = match text[authority_start..authority_end].find('@') {
+            Some(index) => {
+                let userinfo_end = authority_start + index;
+                let username_end = find(bytes, authority_start, userinfo_end, |b| b == b':');
+                validate(text, authority_start, username_end, is_authority_char, true, UriErrorKind::InvalidHostname, UriComponent::UserInfo)?;
+                validate_utf8(text, authority_start, username_end, |_| false, UriComponent::UserInfo)?;
+                if username_end < userinfo_end {
+                    validate(text, username_end + 1, userinfo_end, is_authority_char, true, UriErrorKind::InvalidHostname, UriComponent::UserInfo)?;
+                    validate_utf8(text, username_end + 1, userinfo_end, |_| false, UriComponent::UserInfo)?;
+                    (Some(&text[authority_start..username_end]), Some(&text[(username_end + 1)..userinfo_end]), userinfo_end + 1)
+                } else {
+                    (Some(&text[authority_start..userinfo_end]), None, userinfo_end + 1)
+                }
+            },
+            None => (None, None, authority_start)
+        };
+
+        // host is either an IPv6 address in brackets or a hostname
+        let host_end = if Some(&b'[') == bytes.get(host_start) {
+            let address_end = find(bytes, host_start + 1, authority_end, |b| b == b']');
+            let zone_start = find(bytes, host_start + 1, address_end, |b| b == b'%');
+            validate(text, host_start + 1, zone_start, |c| c.is_ascii_hexdigit() || (c == ':') || (c == '.'), false, UriErrorKind::InvalidIPv6, UriComponent::Host)?;
+
+            // zone identifier must begin with an encoded '%' (RFC 6874)
+            if zone_start < address_end {
+                if !text[zone_start..address_end].starts_with("%25") {
+                    return Err(error(text, zone_start, UriErrorKind::InvalidIPv6, UriComponent::Host));
+                }
+                if (zone_start + 3) == address_end {
+                    return Err(error(text, address_end, UriErrorKind::InvalidIPv6, UriComponent::Host));
+                }
+                validate(text, zone_start + 3, address_end, |c| c.is_ascii_alphanumeric() || (c == '-') || (c == '.') || (c == '_') || (c == '~'), true, UriErrorKind::InvalidIPv6, UriComponent::Host)?;
+            }
+            if address_end == authority_end {
+                return Err(error(text, authority_end, UriErrorKind::InvalidIPv6, UriComponent::Host));
+            }
+
+            // address must be a well-formed IPv6 address
+            if text[(host_start + 1)..zone_start].parse::<Ipv6Addr>().is_err() {
+                return Err(error(text, host_start + 1, UriErrorKind::InvalidIPv6, UriComponent::Host));
+            }
+            if ((address_end + 1) < authority_end) && (b':' != bytes[address_end + 1]) {
+                return Err(error(text, address_end + 1, UriErrorKind::InvalidIPv6, UriComponent::Host));
+            }
+            address_end + 1
+        } else {
+            let hostname_end = find(bytes, host_start, authority_end, |b| b == b':');
+
+            // schemes that require an authority cannot have an empty host
+            if (host_start == hostname_end) && authority_required {
+                return Err(error(text, authority_start, UriErrorKind::InvalidHostname, UriComponent::Host));
+            }
+
+            // hostname cannot contain encoded characters
+            validate(text, host_start, hostname_end, |c| is_authority_char(c) && (c != '%'), false, UriErrorKind::InvalidHostname, UriComponent::Host)?;
+            domain_to_ascii(&text[host_start..hostname_end], host_start)?;
+            hostname_end
+        };
+
+        // port number follows ':'
+        let port = if host_end < authority_end {
+            let port_start = host_end + 1;
+            validate(text, port_start, authority_end, |c| c.is_ascii_digit(), false, UriErrorKind::InvalidPortNumber, UriComponent::Port)?;
+            match text[port_start..authority_end].parse::<u16>() {
+                Ok(port) => Some(port),
+                Err(_) => return Err(error(text, port_start, UriErrorKind::InvalidPortNumber, UriComponent::Port))
+            }
+        } else {
+            None
+        };
+
+        // path ends at the first '?' or '#'
+        let path_end = find(bytes, authority_end, bytes.len(), |b| (b == b'?') || (b == b'#'));
+        validate(text, authority_end, path_end, |c| is_path_char(c) || (c == '/') || (c == '\\'), true, UriErrorKind::InvalidPath, UriComponent::Path)?;
+        validate_utf8(text, authority_end, path_end, is_path_separator, UriComponent::Path)?;
+
+        let (query, fragment) = split_query_and_fragment(text, path_end)?;
+        return Ok(PlugRef {
+            text: text,
+            scheme: &text[..scheme_end],
+            username: username,
+            password: password,
+            host: &text[host_start..host_end],
+            port: port,
+            path: &text[authority_end..path_end],
+            query: query,
+            fragment: fragment,
+            opaque: false
+        });
+    }
+
+    pub fn as_str(&self) -> &'a str {
+        return self.text;
+    }
+
+    pub fn get_scheme(&self) -> &'a str {
+        return self.scheme;
+    }
+
+    pub fn get_username(&self) -> Option<&'a str> {
+        return self.username;
+    }
+
+    pub fn get_password(&self) -> Option<&'a str> {
+        return self.password;
+    }
+
+    pub fn get_host(&self) -> &'a str {
+        return self.host;
+    }
+
+    pub fn get_port(&self) -> Option<u16> {
+        return self.port;
+    }
+
+    pub fn get_path(&self) -> &'a str {
+        return self.path;
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        return self.opaque;
+    }
+
+    pub fn get_segments(&self) -> impl Iterator<Item = &'a str> {
+
+        // opaque path has no segments and trailing '/' does not start another segment
+        let path = if self.opaque {
+            ""
+        } else if self.get_trailing_slash() {
+            &self.path[..(self.path.len() - 1)]
+        } else {
+            self.path
+        };
+        return path.split(['/', '\\']).skip(1);
+    }
+
+    pub fn get_trailing_slash(&self) -> bool {
+        return !self.opaque && (self.path.ends_with('/') || self.path.ends_with('\\'));
+    }
+
+    pub fn get_query(&self) -> Option<&'a str> {
+        return self.query;
+    }
+
+    pub fn get_query_params(&self) -> impl Iterator<Item = (&'a str, Option<&'a str>)> {
+
+        // an empty query has no parameters
+        return self.query.into_iter().filter(|query| !query.is_empty()).flat_map(|query| query.split('&')).map(|param| {
+            match param.find('=') {
+                Some(index) => return (&param[..index], Some(&param[(index + 1)..])),
+                None => return (param, None)
+            }
+        });
+    }
+
+    pub fn get_fragment(&self) -> Option<&'a str> {
+        return self.fragment;
+    }
+
+    pub fn to_plug(&self) -> Result<Plug, PlugParserError> {
+
+        // the text has passed the same checks as Plug::parse; parsing it again decodes the components
+        return Plug::parse(self.text);
+    }
+}
+
+impl<'a> TryFrom<PlugRef<'a>> for Plug {
+    type Error = PlugParserError;
+
+    fn try_from(plug: PlugRef<'a>) -> Result<Plug, PlugParserError> {
+        return plug.to_plug();
+    }
+}
+
+impl<'a> fmt::Display for PlugRef<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return f.write_str(self.text);
+    }
+}
+
+impl<'a> fmt::Debug for PlugRef<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+        // password is replaced with '***', both on its own and within the text
+        let text = match self.password {
+            Some(password) => {
+                let start = (password.as_ptr() as usize) - (self.text.as_ptr() as usize);
+                format!("{}***{}", &self.text[..start], &self.text[(start + password.len())..])
+            },
+            None => String::from(self.text)
+        };
+        return f.debug_struct("PlugRef")
+            .field("text", &text)
+            .field("scheme", &self.scheme)
+            .field("username", &self.username)
+            .field("password", &self.password.map(|_| "***"))
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("path", &self.path)
+            .field("query", &self.query)
+            .field("fragment", &self.fragment)
+            .field("opaque", &self.opaque)
+            .finish();
+    }
+}
+
+fn split_query_and_fragment(text: &str, path_end: usize) -> Result<(Option<&str>, Option<&str>), PlugParserError> {
+    let bytes = text.as_bytes();
+
+    // query begins with '?' and ends at the first '#'
+    let query_end = if Some(&b'?') == bytes.get(path_end) {
+        find(bytes, path_end + 1, bytes.len(), |b| b == b'#')
+    } else {
+        path_end
+    };
+    let query = if query_end > path_end {
+        validate(text, path_end + 1, query_end, |c| is_path_char(c) || (c == '/') || (c == '?'), true, UriErrorKind::InvalidQuery, UriComponent::Query)?;
+        validate_utf8(text, path_end + 1, query_end, |b| (b == b'&') || (b == b'='), UriComponent::Query)?;
+        Some(&text[(path_end + 1)..query_end])
+    } else {
+        None
+    };
+
+    // fragment begins with '#' and extends to the end
+    let fragment = if query_end < bytes.len() {
+        validate(text, query_end + 1, bytes.len(), |c| is_path_char(c) || (c == '/') || (c == '?'), true, UriErrorKind::InvalidFragment, UriComponent::Fragment)?;
+        validate_utf8(text, query_end + 1, bytes.len(), |_| false, UriComponent::Fragment)?;
+        Some(&text[(query_end + 1)..])
+    } else {
+        None
+    };
+    return Ok((query, fragment));
+}
+
+fn find(bytes: &[u8], start: usize, end: usize, predicate: fn(u8) -> bool) -> usize {
+    return bytes[start..end].iter().position(|&b| predicate(b)).map_or(end, |index| start + index);
+}
+
+fn validate(text: &str, start: usize, end: usize, is_valid: fn(char) -> bool, escapes: bool, kind: UriErrorKind, component: UriComponent) -> Result<(), PlugParserError> {
+    let bytes = text.as_bytes();
+    let mut index = start;
+    while index < end {
+        let c = match text[index..].chars().next() {
+            Some(c) => c,
+            None => break
+        };
+        if escapes && (c == '%') {
+
+            // escape sequence must be followed by two hex digits
+            if ((index + 3) > end) || !bytes[index + 1].is_ascii_hexdigit() || !bytes[index + 2].is_ascii_hexdigit() {
+                return Err(error(text, index, UriErrorKind::InvalidEncoding, component));
+            }
+            index += 3;
+        } else if is_valid(c) {
+            index += c.len_utf8();
+        } else {
+            return Err(error(text, index, kind, component));
+        }
+    }
+    return Ok(());
+}
+
+fn validate_utf8(text: &str, start: usize, end: usize, is_separator: fn(u8) -> bool, component: UriComponent) -> Result<(), PlugParserError> {
+
+    // each part between separators is decoded on its own, so each must decode to valid UTF-8
+    let mut part_start = start;
+    while part_start <= end {
+        let part_end = find(text.as_bytes(), part_start, end, is_separator);
+        let part = &text[part_start..part_end];
+        if !decodes_to_utf8(part) {
+            return Err(error(text, part_start + part.find('%').unwrap_or(0), UriErrorKind::InvalidEncoding, component));
+        }
+        part_start = part_end + 1;
+    }
+    return Ok(());
+}
+
+fn decodes_to_utf8(part: &str) -> bool {
+
+    // decoded bytes are checked as they are produced, without collecting them (RFC 3629, section 4)
+    let bytes = part.as_bytes();
+    let mut index = 0;
+    let mut needed = 0;
+    let mut lower = 0x80;
+    let mut upper = 0xBF;
+    while index < bytes.len() {
+        let b = if (bytes[index] == b'%') && ((index + 2) < bytes.len()) {
+            index += 3;
+            match u8::from_str_radix(&part[(index - 2)..index], 16) {
+                Ok(b) => b,
+                Err(_) => return false
+            }
+        } else {
+            index += 1;
+            bytes[index - 1]
+        };
+        if needed == 0 {
+            match b {
+                0x00..=0x7F => (),
+                0xC2..=0xDF => needed = 1,
+                0xE0 => { needed = 2; lower = 0xA0; },
+                0xED => { needed = 2; upper = 0x9F; },
+                0xE1..=0xEF => needed = 2,
+                0xF0 => { needed = 3; lower = 0x90; },
+                0xF4 => { needed = 3; upper = 0x8F; },
+                0xF1..=0xF3 => needed = 3,
+                _ => return false
+            }
+        } else {
+            if (b < lower) || (b > upper) {
+                return false;
+            }
+            lower = 0x80;
+            upper = 0xBF;
+            needed -= 1;
+        }
+    }
+    return needed == 0;
+}
+
+fn is_path_separator(b: u8) -> bool {
+    return (b == b'/') || (b == b'\\') || (b == b';') || (b == b'=');
+}
+
+fn error(text: &str, offset: usize, kind: UriErrorKind, component: UriComponent) -> PlugParserError {
+    return UriParserError::new(kind, component, offset, text[offset..].chars().next()).into();
+}
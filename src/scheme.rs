@@ -0,0 +1,147 @@
+/*
+ * RustyPlug - a rust module with a fluid interface for building requests to sockets
+ *
+ * Copyright (C) 2016 Steve G. Bjorg
+ *
+ * For community documentation and downloads visit mindtouch.com;
+ * please review the licensing section.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+use plug::PlugNormalization;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scheme {
+    default_port: Option<u16>,
+    authority_required: bool,
+    normalization: PlugNormalization
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemeRegistry {
+    schemes: HashMap<String, Scheme>
+}
+
+static GLOBAL_REGISTRY: OnceLock<RwLock<SchemeRegistry>> = OnceLock::new();
+
+impl Scheme {
+    pub fn new() -> Scheme {
+        return Scheme {
+            default_port: None,
+            authority_required: false,
+            normalization: PlugNormalization::all()
+        };
+    }
+
+    pub fn get_default_port(&self) -> Option<u16> {
+        return self.default_port;
+    }
+
+    pub fn get_authority_required(&self) -> bool {
+        return self.authority_required;
+    }
+
+    pub fn get_normalization(&self) -> PlugNormalization {
+        return self.normalization;
+    }
+
+    pub fn with_default_port(&self, port: u16) -> Scheme {
+        return Scheme { default_port: Some(port), ..*self };
+    }
+
+    pub fn without_default_port(&self) -> Scheme {
+        return Scheme { default_port: None, ..*self };
+    }
+
+    pub fn with_authority_required(&self, required: bool) -> Scheme {
+        return Scheme { authority_required: required, ..*self };
+    }
+
+    pub fn with_normalization(&self, normalization: PlugNormalization) -> Scheme {
+        return Scheme { normalization: normalization, ..*self };
+    }
+}
+
+impl Default for Scheme {
+    fn default() -> Scheme {
+        return Scheme::new();
+    }
+}
+
+impl SchemeRegistry {
+    pub fn new() -> SchemeRegistry {
+        let web = Scheme::new().with_authority_required(true);
+        let mut registry = SchemeRegistry::empty();
+        registry.register("http", web.with_default_port(80));
+        registry.register("https", web.with_default_port(443));
+        registry.register("ws", web.with_default_port(80));
+        registry.register("wss", web.with_default_port(443));
+        registry.register("ftp", web.with_default_port(21));
+        registry.register("file", Scheme::new());
+        return registry;
+    }
+
+    pub fn empty() -> SchemeRegistry {
+        return SchemeRegistry {
+            schemes: HashMap::new()
+        };
+    }
+
+    pub fn global() -> SchemeRegistry {
+        return global_registry().read().unwrap_or_else(|error| error.into_inner()).clone();
+    }
+
+    pub fn register_global(name: &str, scheme: Scheme) {
+
+        // parsing, rendering, comparing and normalizing all consult the global registry, so schemes must be
+        // registered at startup, before any plug with that scheme is parsed or built; plugs that already exist
+        // keep their stored port but would render and compare under the new rules
+        global_registry().write().unwrap_or_else(|error| error.into_inner()).register(name, scheme);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Scheme> {
+
+        // scheme names are case-insensitive and stored in lowercase
+        if !name.bytes().any(|b| b.is_ascii_uppercase()) {
+            return self.schemes.get(name);
+        }
+        return self.schemes.iter().find(|&(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, scheme)| scheme);
+    }
+
+    pub fn register(&mut self, name: &str, scheme: Scheme) {
+        self.schemes.insert(name.to_ascii_lowercase(), scheme);
+    }
+
+    pub fn unregister(&mut self, name: &str) -> Option<Scheme> {
+        return self.schemes.remove(&name.to_ascii_lowercase());
+    }
+}
+
+impl Default for SchemeRegistry {
+    fn default() -> SchemeRegistry {
+        return SchemeRegistry::new();
+    }
+}
+
+pub(crate) fn lookup(name: &str) -> Option<Scheme> {
+    return global_registry().read().unwrap_or_else(|error| error.into_inner()).get(name).copied();
+}
+
+fn global_registry() -> &'static RwLock<SchemeRegistry> {
+    return GLOBAL_REGISTRY.get_or_init(|| RwLock::new(SchemeRegistry::new()));
+}
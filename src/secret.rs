@@ -0,0 +1,78 @@
+/*
+ * RustyPlug - a rust module with a fluid interface for building requests to sockets
+ *
+ * Copyright (C) 2016 Steve G. Bjorg
+ *
+ * For community documentation and downloads visit mindtouch.com;
+ * please review the licensing section.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::fmt;
+use zeroize::Zeroize;
+
+#[derive(Clone, PartialEq)]
+pub struct Secret {
+    value: String
+}
+
+impl Secret {
+    pub fn new(value: String) -> Secret {
+        return Secret {
+            value: value
+        };
+    }
+
+    pub fn expose(&self) -> &str {
+        return &self.value;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.value.is_empty();
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+
+        // clear the value before its memory is released
+        self.value.zeroize();
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return f.write_str("***");
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return f.write_str("***");
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Secret {
+        return Secret::new(value);
+    }
+}
+
+impl<'a> From<&'a str> for Secret {
+    fn from(value: &'a str) -> Secret {
+        return Secret::new(value.into());
+    }
+}
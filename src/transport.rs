@@ -0,0 +1,294 @@
+/*
+ * RustyPlug - a rust module with a fluid interface for building requests to sockets
+ *
+ * Copyright (C) 2016 Steve G. Bjorg
+ *
+ * For community documentation and downloads visit mindtouch.com;
+ * please review the licensing section.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+use plug::Plug;
+
+const DEFAULT_MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnixTransport {
+    timeout: Option<Duration>,
+    max_body_size: usize
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HttpResponse {
+    status: u16,
+    reason: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>
+}
+
+#[derive(Debug)]
+pub enum TransportError {
+    InvalidHost,
+    InvalidRequest,
+    InvalidResponse,
+    BodyTooLarge,
+    Io(io::Error)
+}
+
+impl UnixTransport {
+    pub fn new() -> UnixTransport {
+        return UnixTransport {
+            timeout: None,
+            max_body_size: DEFAULT_MAX_BODY_SIZE
+        };
+    }
+
+    pub fn get_timeout(&self) -> Option<Duration> {
+        return self.timeout;
+    }
+
+    pub fn get_max_body_size(&self) -> usize {
+        return self.max_body_size;
+    }
+
+    pub fn with_timeout(&self, timeout: Duration) -> UnixTransport {
+        return UnixTransport { timeout: Some(timeout), ..self.clone() };
+    }
+
+    pub fn without_timeout(&self) -> UnixTransport {
+        return UnixTransport { timeout: None, ..self.clone() };
+    }
+
+    pub fn with_max_body_size(&self, max_body_size: usize) -> UnixTransport {
+        return UnixTransport { max_body_size: max_body_size, ..self.clone() };
+    }
+
+    pub fn send(&self, method: &str, plug: &Plug, headers: &[(String, String)], body: &[u8]) -> Result<HttpResponse, TransportError> {
+
+        // plug must have a socket path as its host (e.g. 'http+unix://%2Fvar%2Frun%2Fapp.sock/')
+        let path = match plug.get_host().get_socket_path() {
+            Some(path) => path,
+            None => return Err(TransportError::InvalidHost)
+        };
+
+        // method and headers are written verbatim, so they must not be able to end a line
+        if !is_token(method) || headers.iter().any(|(name, value)| !is_token(name) || !is_header_value(value)) {
+            return Err(TransportError::InvalidRequest);
+        }
+        let mut stream = UnixStream::connect(path)?;
+        stream.set_read_timeout(self.timeout)?;
+        stream.set_write_timeout(self.timeout)?;
+
+        // request is sent with 'Connection: close' so the response ends with the stream
+        let mut request = Vec::new();
+        write!(request, "{} {} HTTP/1.1\r\n", method, plug.path_and_query())?;
+        if !headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("host")) {
+            request.extend_from_slice(b"Host: localhost\r\n");
+        }
+        for (name, value) in headers.iter() {
+            write!(request, "{}: {}\r\n", name, value)?;
+        }
+
+        // body length is sent unless the caller framed the body already; methods that expect a body get it even when empty
+        let framed = headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("content-length") || name.eq_ignore_ascii_case("transfer-encoding"));
+        let expects_body = ["POST", "PUT", "PATCH"].iter().any(|name| method.eq_ignore_ascii_case(name));
+        if !framed && (!body.is_empty() || expects_body) {
+            write!(request, "Content-Length: {}\r\n", body.len())?;
+        }
+        request.extend_from_slice(b"Connection: close\r\n\r\n");
+        request.extend_from_slice(body);
+        stream.write_all(&request)?;
+        stream.flush()?;
+        return read_response(&mut BufReader::new(stream), method.eq_ignore_ascii_case("HEAD"), self.max_body_size);
+    }
+}
+
+impl Default for UnixTransport {
+    fn default() -> UnixTransport {
+        return UnixTransport::new();
+    }
+}
+
+impl HttpResponse {
+    pub fn get_status(&self) -> u16 {
+        return self.status;
+    }
+
+    pub fn get_reason(&self) -> &str {
+        return &self.reason;
+    }
+
+    pub fn get_headers(&self) -> &[(String, String)] {
+        return &self.headers;
+    }
+
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        return self.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str());
+    }
+
+    pub fn get_body(&self) -> &[u8] {
+        return &self.body;
+    }
+
+    pub fn into_body(self) -> Vec<u8> {
+        return self.body;
+    }
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TransportError::InvalidHost => return f.write_str("host is not a unix socket path"),
+            TransportError::InvalidRequest => return f.write_str("invalid HTTP method or header"),
+            TransportError::InvalidResponse => return f.write_str("invalid HTTP response"),
+            TransportError::BodyTooLarge => return f.write_str("HTTP response body is too large"),
+            TransportError::Io(ref error) => return write!(f, "I/O error: {}", error)
+        }
+    }
+}
+
+impl Error for TransportError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            TransportError::Io(ref error) => return Some(error),
+            _ => return None
+        }
+    }
+}
+
+impl From<io::Error> for TransportError {
+    fn from(error: io::Error) -> TransportError {
+        return TransportError::Io(error);
+    }
+}
+
+fn read_response<R: BufRead>(reader: &mut R, head: bool, max_body_size: usize) -> Result<HttpResponse, TransportError> {
+
+    // status line is 'HTTP/1.x <status> <reason>'
+    let line = read_line(reader)?;
+    let mut parts = line.splitn(3, ' ');
+    match parts.next() {
+        Some(version) if version.starts_with("HTTP/") => (),
+        _ => return Err(TransportError::InvalidResponse)
+    }
+    let status = match parts.next().map(|status| status.parse::<u16>()) {
+        Some(Ok(status)) => status,
+        _ => return Err(TransportError::InvalidResponse)
+    };
+    let reason = parts.next().unwrap_or("").to_string();
+
+    // headers end with an empty line
+    let mut headers = Vec::new();
+    loop {
+        let line = read_line(reader)?;
+        if line.is_empty() {
+            break;
+        }
+        match line.find(':') {
+            Some(index) => headers.push((line[..index].trim().to_string(), line[(index + 1)..].trim().to_string())),
+            None => return Err(TransportError::InvalidResponse)
+        }
+    }
+    let mut response = HttpResponse {
+        status: status,
+        reason: reason,
+        headers: headers,
+        body: Vec::new()
+    };
+
+    // body is delimited by chunked encoding, by its length or by the end of the stream
+    if head || (100..200).contains(&status) || (status == 204) || (status == 304) {
+        return Ok(response);
+    }
+    let chunked = response.get_header("transfer-encoding").is_some_and(|encoding| encoding.to_lowercase().contains("chunked"));
+    if chunked {
+        loop {
+            let line = read_line(reader)?;
+            let size = line.split(';').next().unwrap_or("").trim();
+            let size = usize::from_str_radix(size, 16).map_err(|_| TransportError::InvalidResponse)?;
+            if size == 0 {
+
+                // trailers are skipped
+                while !read_line(reader)?.is_empty() {}
+                break;
+            }
+            let length = match response.body.len().checked_add(size) {
+                Some(length) if length <= max_body_size => length,
+                _ => return Err(TransportError::BodyTooLarge)
+            };
+            read_body(reader, &mut response.body, length)?;
+            if !read_line(reader)?.is_empty() {
+                return Err(TransportError::InvalidResponse);
+            }
+        }
+    } else if let Some(length) = response.get_header("content-length") {
+        let length = length.parse::<usize>().map_err(|_| TransportError::InvalidResponse)?;
+        if length > max_body_size {
+            return Err(TransportError::BodyTooLarge);
+        }
+        read_body(reader, &mut response.body, length)?;
+    } else {
+
+        // one byte past the limit is enough to tell that the body is too large
+        reader.take((max_body_size as u64).saturating_add(1)).read_to_end(&mut response.body)?;
+        if response.body.len() > max_body_size {
+            return Err(TransportError::BodyTooLarge);
+        }
+    }
+    return Ok(response);
+}
+
+fn read_body<R: Read>(reader: &mut R, body: &mut Vec<u8>, length: usize) -> Result<(), TransportError> {
+
+    // body grows as data arrives rather than being sized up front from the announced length
+    let missing = length - body.len();
+    reader.take(missing as u64).read_to_end(body)?;
+    if body.len() < length {
+        return Err(TransportError::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "response body ended early")));
+    }
+    return Ok(());
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> Result<String, TransportError> {
+    let mut buffer = Vec::new();
+    if reader.read_until(b'\n', &mut buffer)? == 0 {
+        return Err(TransportError::InvalidResponse);
+    }
+
+    // line ends with '\r\n', but a bare '\n' is tolerated
+    while (Some(&b'\n') == buffer.last()) || (Some(&b'\r') == buffer.last()) {
+        buffer.pop();
+    }
+    return String::from_utf8(buffer).map_err(|_| TransportError::InvalidResponse);
+}
+
+fn is_token(text: &str) -> bool {
+
+    // token characters as defined by RFC 9110
+    return !text.is_empty() && text.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b));
+}
+
+fn is_header_value(text: &str) -> bool {
+
+    // tabs are allowed, but no other control characters
+    return text.bytes().all(|b| (b == b'\t') || ((b >= b' ') && (b != 0x7F)));
+}
@@ -19,8 +19,23 @@
  * limitations under the License.
  */
 
-use std::str::Chars;
-use std::iter::Peekable;
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::error::Error;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+use idna;
+use encoding::{decode, encode_opaque_path, DecodeError};
+use form_urlencoded::{self, Separator};
+use host::Host;
+
+#[derive(Clone, Debug)]
+pub struct UriChars<'a> {
+    text: &'a str,
+    offset: usize,
+    current: Option<char>
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum UriCredentials {
@@ -29,31 +44,228 @@ pub enum UriCredentials {
     UsernamePassword(String, String)
 }
 
-#[derive(Clone, Debug, PartialEq)]
-pub enum UriParserError {
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UriComponent {
+    Scheme,
+    Authority,
+    UserInfo,
+    Host,
+    Port,
+    Path,
+    Query,
+    Fragment
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UriErrorKind {
     InternalError,
     InvalidScheme,
     InvalidHostname,
+    InvalidDomainName,
     InvalidIPv6,
-    InvalidPortNumber
+    InvalidPortNumber,
+    InvalidPath,
+    InvalidQuery,
+    InvalidFragment,
+    InvalidEncoding,
+    MissingColonSlashSlash
+}
+
+// parameters are key and optional value pairs
+pub type Params = Vec<(String, Option<String>)>;
+
+// path segment is a decoded name with its decoded matrix parameters
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    name: String,
+    params: Params
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UriReference {
+    pub scheme: Option<String>,
+    pub authority: Option<(UriCredentials, Host, Option<u16>)>,
+    pub opaque: Option<String>,
+    pub absolute: bool,
+    pub segments: Vec<Segment>,
+    pub trailing_slash: bool,
+    pub query: Option<Params>,
+    pub fragment: Option<String>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UriParserError {
+    pub kind: UriErrorKind,
+    pub component: UriComponent,
+    pub offset: usize,
+    pub found: Option<char>
 }
 
-pub fn parse_scheme(parser: &mut Peekable<Chars>) -> Result<String, UriParserError> {
+impl Segment {
+    pub fn new(name: String) -> Segment {
+        return Segment {
+            name: name,
+            params: Vec::new()
+        };
+    }
+
+    pub fn new_with_params(name: String, params: Params) -> Segment {
+        return Segment {
+            name: name,
+            params: params
+        };
+    }
+
+    pub fn get_name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn get_params(&self) -> &[(String, Option<String>)] {
+        return &self.params;
+    }
+
+    pub fn get_param(&self, key: &str) -> Option<&str> {
+
+        // flags have no value and are only seen as present
+        return self.params.iter().find(|(name, _)| name == key).and_then(|(_, value)| value.as_deref());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.name.is_empty() && self.params.is_empty();
+    }
+
+    pub fn with_param(&self, key: String, value: String) -> Segment {
+        let mut new_params = self.params.clone();
+        new_params.push((key, Some(value)));
+        return Segment { params: new_params, ..self.clone() };
+    }
+
+    pub fn with_flag(&self, key: String) -> Segment {
+        let mut new_params = self.params.clone();
+        new_params.push((key, None));
+        return Segment { params: new_params, ..self.clone() };
+    }
+}
+
+impl From<String> for Segment {
+    fn from(name: String) -> Segment {
+        return Segment::new(name);
+    }
+}
+
+impl<'a> From<&'a str> for Segment {
+    fn from(name: &'a str) -> Segment {
+        return Segment::new(name.into());
+    }
+}
+
+impl<'a> UriChars<'a> {
+    pub fn new(text: &'a str) -> UriChars<'a> {
+        return UriChars {
+            text: text,
+            offset: 0,
+            current: text.chars().next()
+        };
+    }
+
+    pub fn peek(&self) -> Option<&char> {
+        return self.current.as_ref();
+    }
+
+    pub fn offset(&self) -> usize {
+        return self.offset;
+    }
+
+    pub fn as_str(&self) -> &'a str {
+        return &self.text[self.offset..];
+    }
+}
+
+impl<'a> Iterator for UriChars<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.current?;
+        self.offset += c.len_utf8();
+        self.current = self.text[self.offset..].chars().next();
+        return Some(c);
+    }
+}
+
+impl UriParserError {
+    pub fn new(kind: UriErrorKind, component: UriComponent, offset: usize, found: Option<char>) -> UriParserError {
+        return UriParserError {
+            kind: kind,
+            component: component,
+            offset: offset,
+            found: found
+        };
+    }
+}
+
+impl fmt::Display for UriComponent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return f.write_str(match *self {
+            UriComponent::Scheme => "scheme",
+            UriComponent::Authority => "authority",
+            UriComponent::UserInfo => "user-info",
+            UriComponent::Host => "host",
+            UriComponent::Port => "port",
+            UriComponent::Path => "path",
+            UriComponent::Query => "query",
+            UriComponent::Fragment => "fragment"
+        });
+    }
+}
+
+impl fmt::Display for UriErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return f.write_str(match *self {
+            UriErrorKind::InternalError => "internal error",
+            UriErrorKind::InvalidScheme => "invalid scheme",
+            UriErrorKind::InvalidHostname => "invalid hostname",
+            UriErrorKind::InvalidDomainName => "invalid internationalized domain name",
+            UriErrorKind::InvalidIPv6 => "invalid IPv6 address",
+            UriErrorKind::InvalidPortNumber => "invalid port number",
+            UriErrorKind::InvalidPath => "invalid path",
+            UriErrorKind::InvalidQuery => "invalid query",
+            UriErrorKind::InvalidFragment => "invalid fragment",
+            UriErrorKind::InvalidEncoding => "invalid percent-encoding",
+            UriErrorKind::MissingColonSlashSlash => "missing '://'"
+        });
+    }
+}
+
+impl fmt::Display for UriParserError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write_error(f, self.kind, self.component, self.offset, self.found);
+    }
+}
+
+impl Error for UriParserError {}
+
+pub fn write_error(f: &mut fmt::Formatter, kind: UriErrorKind, component: UriComponent, offset: usize, found: Option<char>) -> fmt::Result {
+    match found {
+        Some(c) => return write!(f, "{} in {} at offset {}: unexpected {:?}", kind, component, offset, c),
+        None => return write!(f, "{} in {} at offset {}: unexpected end of input", kind, component, offset)
+    }
+}
+
+pub fn parse_scheme(parser: &mut UriChars) -> Result<String, UriParserError> {
 
     // scheme must begin with alphabetic character
-    match parser.next() {
-        Some(first) if
-            ((first >= 'a') && (first <= 'z'))
-            || ((first >= 'A') && (first <= 'Z'))
-        => {
+    match parser.peek() {
+        Some(&first) if first.is_ascii_alphabetic() => {
+            parser.next();
             let mut buffer = String::new();
             buffer.push(first);
             loop {
                 match parser.peek() {
                     Some(&c) if
-                        ((c >= 'a') && (c <= 'z'))
-                        || ((c >= 'A') && (c <= 'Z'))
-                        || ((c >= '0') && (c <= '9'))
+                        c.is_ascii_alphanumeric()
+                        || (c == '+')
+                        || (c == '-')
+                        || (c == '.')
                     => {
                         parser.next();
 
@@ -63,42 +275,59 @@ pub fn parse_scheme(parser: &mut Peekable<Chars>) -> Result<String, UriParserErr
                     Some(&':') | None => {
                         return Ok(buffer);
                     }
-                    _ => return Err(UriParserError::InvalidScheme)
+                    _ => return Err(unexpected(parser, UriErrorKind::InvalidScheme, UriComponent::Scheme))
                 }
             }
         },
-        _ => return Err(UriParserError::InvalidScheme)
+        _ => return Err(unexpected(parser, UriErrorKind::InvalidScheme, UriComponent::Scheme))
     };
 }
 
-pub fn parse_authority(mut parser: &mut Peekable<Chars>) -> Result<(UriCredentials,String,Option<u16>), UriParserError> {
-    fn parse_hostname_or_userinfo(mut parser: &mut Peekable<Chars>) -> Result<(UriCredentials,String,Option<u16>), UriParserError> {
+pub fn is_unix_socket_scheme(scheme: &str) -> bool {
+
+    // scheme like 'http+unix' has a socket path instead of a hostname
+    return (scheme.len() > 5) && scheme.is_char_boundary(scheme.len() - 5) && scheme[(scheme.len() - 5)..].eq_ignore_ascii_case("+unix");
+}
+
+pub fn parse_socket_authority(parser: &mut UriChars) -> Result<(UriCredentials,Host,Option<u16>), UriParserError> {
+
+    // socket path is percent-encoded, since it cannot contain '/'
+    let mut buffer = String::new();
+    let start = parser.offset();
+    loop {
+        match parser.peek() {
+            Some(&c) if is_authority_char(c) => {
+                parser.next();
+
+                // valid character, keep parsing
+                buffer.push(c);
+            },
+            Some(&'/') | Some(&'\\') | Some(&'?') | Some(&'#') | None if !buffer.is_empty() => {
+                let path = uri_decode(&buffer, start, UriComponent::Host)?;
+                return Ok((UriCredentials::None, Host::UnixSocket(PathBuf::from(path)), None));
+            },
+            _ => return Err(unexpected(parser, UriErrorKind::InvalidHostname, UriComponent::Host))
+        }
+    }
+}
+
+pub fn parse_authority(parser: &mut UriChars) -> Result<(UriCredentials,Host,Option<u16>), UriParserError> {
+    fn parse_hostname_or_userinfo(parser: &mut UriChars) -> Result<(UriCredentials,Host,Option<u16>), UriParserError> {
         let mut decode = false;
         let mut buffer = String::new();
+        let mut start = parser.offset();
 
         // parse hostname -OR- user-info
         loop {
             match parser.peek() {
-                Some(&c) if (c == '%') || (c <= '+') => {
+                Some(&'%') => {
                     parser.next();
                     decode = true;
 
                     // potentially valid character, keep parsing
-                    buffer.push(c);
+                    buffer.push('%');
                 },
-                Some(&c) if
-                    ((c >= 'a') && (c <= 'z'))
-                    || ((c >= 'A') && (c <= 'Z'))
-                    || ((c >= '0') && (c <= '9'))
-                    || ((c >= '$') && (c <= '.'))   // one of: $%&'()*+,-.
-                    || (c == '!')
-                    || (c == ';')
-                    || (c == '=')
-                    || (c == '_')
-                    || (c == '~')
-                    || c.is_numeric()
-                    || c.is_alphabetic()
-                => {
+                Some(&c) if is_authority_char(c) => {
                     parser.next();
 
                     // valid character, keep parsing
@@ -112,30 +341,20 @@ pub fn parse_authority(mut parser: &mut Peekable<Chars>) -> Result<(UriCredentia
                     decode = false;
                     let hostname_or_username = buffer;
                     buffer = String::new();
+                    let hostname_or_username_start = start;
+                    start = parser.offset();
 
                     // parse either password -OR- port number
                     loop {
                         match parser.peek() {
-                            Some(&c) if (c == '%') || (c <= '+') => {
+                            Some(&'%') => {
                                 parser.next();
                                 decode = true;
 
                                 // potentially valid character, keep parsing
-                                buffer.push(c);
+                                buffer.push('%');
                             },
-                            Some(&c) if
-                                ((c >= 'a') && (c <= 'z'))
-                                || ((c >= 'A') && (c <= 'Z'))
-                                || ((c >= '0') && (c <= '9'))
-                                || ((c >= '$') && (c <= '.'))   // one of: $%&'()*+,-.
-                                || (c == '!')
-                                || (c == ';')
-                                || (c == '=')
-                                || (c == '_')
-                                || (c == '~')
-                                || c.is_numeric()
-                                || c.is_alphabetic()
-                            => {
+                            Some(&c) if is_authority_char(c) => {
                                 parser.next();
 
                                 // valid character, keep parsing
@@ -146,14 +365,14 @@ pub fn parse_authority(mut parser: &mut Peekable<Chars>) -> Result<(UriCredentia
 
                                 // part before ':' was username
                                 let username = if decode_hostname_or_username {
-                                    uri_decode(&hostname_or_username)
+                                    uri_decode(&hostname_or_username, hostname_or_username_start, UriComponent::UserInfo)?
                                 } else {
                                     hostname_or_username
                                 };
 
                                 // part after ':' is password
                                 let password = if decode {
-                                    uri_decode(&buffer)
+                                    uri_decode(&buffer, start, UriComponent::UserInfo)?
                                 } else {
                                     buffer
                                 };
@@ -171,22 +390,22 @@ pub fn parse_authority(mut parser: &mut Peekable<Chars>) -> Result<(UriCredentia
                                 if decode_hostname_or_username {
 
                                     // hostname cannot contain encoded characters
-                                    return Err(UriParserError::InvalidHostname);
+                                    return Err(encoded(&hostname_or_username, hostname_or_username_start, UriErrorKind::InvalidHostname, UriComponent::Host));
                                 }
 
                                 // part after ':' is port
                                 if decode {
 
                                     // port number cannot contain encoded characters
-                                    return Err(UriParserError::InvalidPortNumber);
+                                    return Err(encoded(&buffer, start, UriErrorKind::InvalidPortNumber, UriComponent::Port));
                                 }
-                                if let Ok(port) = buffer.parse::<u16>() {
-                                    return Ok((UriCredentials::None, hostname_or_username, Some(port)));
-                                } else {
-                                    return Err(UriParserError::InvalidPortNumber);
+                                // port number is made of digits only; u16::from_str would also accept a leading '+'
+                                match buffer.parse::<u16>() {
+                                    Ok(port) if buffer.bytes().all(|b| b.is_ascii_digit()) => return Ok((UriCredentials::None, to_host(hostname_or_username, hostname_or_username_start)?, Some(port))),
+                                    _ => return Err(invalid_port(&buffer, start))
                                 }
                             },
-                            Some(_) => return Err(UriParserError::InvalidHostname)
+                            Some(_) => return Err(unexpected(parser, UriErrorKind::InvalidHostname, UriComponent::Authority))
                         }
                     }
                 },
@@ -195,7 +414,7 @@ pub fn parse_authority(mut parser: &mut Peekable<Chars>) -> Result<(UriCredentia
 
                     // part before '@' must be username since we didn't find ':'
                     let credentials = UriCredentials::Username(if decode {
-                        uri_decode(&buffer)
+                        uri_decode(&buffer, start, UriComponent::UserInfo)?
                     } else {
                         buffer
                     });
@@ -210,42 +429,31 @@ pub fn parse_authority(mut parser: &mut Peekable<Chars>) -> Result<(UriCredentia
                     if decode {
 
                         // hostname cannot contain encoded characters
-                        return Err(UriParserError::InvalidHostname);
+                        return Err(encoded(&buffer, start, UriErrorKind::InvalidHostname, UriComponent::Host));
                     }
-                    return Ok((UriCredentials::None, buffer, None));
+                    return Ok((UriCredentials::None, to_host(buffer, start)?, None));
                 },
-                Some(_) => return Err(UriParserError::InvalidHostname)
+                Some(_) => return Err(unexpected(parser, UriErrorKind::InvalidHostname, UriComponent::Authority))
             }
         }
     }
 
-    fn parse_hostname(mut parser: &mut Peekable<Chars>, credentials: UriCredentials) -> Result<(UriCredentials,String,Option<u16>), UriParserError> {
+    fn parse_hostname(parser: &mut UriChars, credentials: UriCredentials) -> Result<(UriCredentials,Host,Option<u16>), UriParserError> {
 
         // parse hostname
         let mut decode = false;
         let mut buffer = String::new();
+        let start = parser.offset();
         loop {
             match parser.peek() {
-                Some(&c) if (c == '%') || (c <= '+') => {
+                Some(&'%') => {
                     parser.next();
                     decode = true;
 
                     // potentially valid character, keep parsing
-                    buffer.push(c);
+                    buffer.push('%');
                 },
-                Some(&c) if
-                    ((c >= 'a') && (c <= 'z'))
-                    || ((c >= 'A') && (c <= 'Z'))
-                    || ((c >= '0') && (c <= '9'))
-                    || ((c >= '$') && (c <= '.'))   // one of: $%&'()*+,-.
-                    || (c == '!')
-                    || (c == ';')
-                    || (c == '=')
-                    || (c == '_')
-                    || (c == '~')
-                    || c.is_numeric()
-                    || c.is_alphabetic()
-                => {
+                Some(&c) if is_authority_char(c) => {
                     parser.next();
 
                     // valid character, keep parsing
@@ -255,9 +463,9 @@ pub fn parse_authority(mut parser: &mut Peekable<Chars>) -> Result<(UriCredentia
                     if decode {
 
                         // hostname cannot contain encoded characters
-                        return Err(UriParserError::InvalidHostname);
+                        return Err(encoded(&buffer, start, UriErrorKind::InvalidHostname, UriComponent::Host));
                     }
-                    return parse_portnumber(parser, credentials, buffer);
+                    return parse_portnumber(parser, credentials, to_host(buffer, start)?);
                 },
                 Some(&'/') | Some(&'\\') | Some(&'?') | Some(&'#') | None => {
 
@@ -265,65 +473,117 @@ pub fn parse_authority(mut parser: &mut Peekable<Chars>) -> Result<(UriCredentia
                     if decode {
 
                         // hostname cannot contain encoded characters
-                        return Err(UriParserError::InvalidHostname);
+                        return Err(encoded(&buffer, start, UriErrorKind::InvalidHostname, UriComponent::Host));
                     }
-                    return Ok((credentials, buffer, None));
+                    return Ok((credentials, to_host(buffer, start)?, None));
                 },
-                Some(_) => return Err(UriParserError::InvalidHostname)
+                Some(_) => return Err(unexpected(parser, UriErrorKind::InvalidHostname, UriComponent::Host))
             }
         }
     }
 
-    fn parse_ipv6(mut parser: &mut Peekable<Chars>, credentials: UriCredentials) -> Result<(UriCredentials,String,Option<u16>), UriParserError> {
+    fn parse_ipv6(parser: &mut UriChars, credentials: UriCredentials) -> Result<(UriCredentials,Host,Option<u16>), UriParserError> {
 
         // IPv6 address must begin with '['
         if Some('[') != parser.next() {
-            return Err(UriParserError::InternalError);
+            return Err(unexpected(parser, UriErrorKind::InternalError, UriComponent::Host));
         }
+        let start = parser.offset();
         let mut buffer = String::new();
-        buffer.push('[');
+        let mut zone_id = None;
 
         // parse IPv6 address
         loop {
             match parser.peek() {
-                Some(&c) if c.is_digit(16) || (c == ':') || (c == '.') => {
+                Some(&c) if (c.is_ascii_hexdigit() || (c == ':') || (c == '.')) && zone_id.is_none() => {
                     parser.next();
 
                     // valid character, keep parsing
                     buffer.push(c);
                 },
+                Some(&'%') if zone_id.is_none() => {
+                    zone_id = Some(parse_zone_id(parser)?);
+                },
                 Some(&']') => {
+
+                    // address must be a well-formed IPv6 address
+                    let address = match buffer.parse::<Ipv6Addr>() {
+                        Ok(address) => address,
+                        Err(_) => return Err(UriParserError::new(UriErrorKind::InvalidIPv6, UriComponent::Host, start, buffer.chars().next().or(Some(']'))))
+                    };
                     parser.next();
-                    buffer.push(']');
+                    let host = Host::Ipv6(address, zone_id);
 
                     // check if there is an optional port number
                     match parser.peek() {
                         Some(&':') => {
-                            return parse_portnumber(parser, credentials, buffer);
+                            return parse_portnumber(parser, credentials, host);
                         },
                         Some(&'/') | Some(&'\\') | Some(&'?') | Some(&'#') | None => {
-                            return Ok((credentials, buffer, None));
+                            return Ok((credentials, host, None));
                         },
-                        Some(_) => return Err(UriParserError::InvalidIPv6)
+                        Some(_) => return Err(unexpected(parser, UriErrorKind::InvalidIPv6, UriComponent::Host))
                     }
                 },
-                _ => return Err(UriParserError::InvalidIPv6)
+                _ => return Err(unexpected(parser, UriErrorKind::InvalidIPv6, UriComponent::Host))
+            }
+        }
+    }
+
+    fn parse_zone_id(parser: &mut UriChars) -> Result<String, UriParserError> {
+
+        // zone identifier must begin with an encoded '%' (RFC 6874)
+        if !parser.as_str().starts_with("%25") {
+            return Err(unexpected(parser, UriErrorKind::InvalidIPv6, UriComponent::Host));
+        }
+        parser.next();
+        parser.next();
+        parser.next();
+        let start = parser.offset();
+        let mut decode = false;
+        let mut buffer = String::new();
+
+        // parse zone identifier
+        loop {
+            match parser.peek() {
+                Some(&'%') => {
+                    parser.next();
+                    decode = true;
+
+                    // potentially valid character, keep parsing
+                    buffer.push('%');
+                },
+                Some(&c) if c.is_ascii_alphanumeric() || (c == '-') || (c == '.') || (c == '_') || (c == '~') => {
+                    parser.next();
+
+                    // valid character, keep parsing
+                    buffer.push(c);
+                },
+                Some(&']') if !buffer.is_empty() => {
+                    return Ok(if decode {
+                        uri_decode(&buffer, start, UriComponent::Host)?
+                    } else {
+                        buffer
+                    });
+                },
+                _ => return Err(unexpected(parser, UriErrorKind::InvalidIPv6, UriComponent::Host))
             }
         }
     }
 
-    fn parse_portnumber(mut parser: &mut Peekable<Chars>, credentials: UriCredentials, hostname: String) -> Result<(UriCredentials,String,Option<u16>), UriParserError> {
+    fn parse_portnumber(parser: &mut UriChars, credentials: UriCredentials, hostname: Host) -> Result<(UriCredentials,Host,Option<u16>), UriParserError> {
 
         // port number must begin with ':'
         if Some(':') != parser.next() {
-            return Err(UriParserError::InternalError);
+            return Err(unexpected(parser, UriErrorKind::InternalError, UriComponent::Port));
         }
         let mut buffer = String::new();
+        let start = parser.offset();
 
         // parse port number
         loop {
             match parser.peek() {
-                Some(&c) if (c >= '0') || (c <= '9') => {
+                Some(&c) if c.is_ascii_digit() => {
                     parser.next();
 
                     // valid character, keep parsing
@@ -333,10 +593,10 @@ pub fn parse_authority(mut parser: &mut Peekable<Chars>) -> Result<(UriCredentia
                     if let Ok(port) = buffer.parse::<u16>() {
                         return Ok((credentials, hostname, Some(port)));
                     } else {
-                        return Err(UriParserError::InvalidPortNumber);
+                        return Err(invalid_port(&buffer, start));
                     }
                 },
-                Some(_) => return Err(UriParserError::InvalidPortNumber)
+                Some(_) => return Err(unexpected(parser, UriErrorKind::InvalidPortNumber, UriComponent::Port))
             }
         }
     }
@@ -348,7 +608,393 @@ pub fn parse_authority(mut parser: &mut Peekable<Chars>) -> Result<(UriCredentia
     return parse_hostname_or_userinfo(parser);
 }
 
-fn uri_decode(_text: &str) -> String {
-    unimplemented!();
+pub fn parse_reference(parser: &mut UriChars, base_scheme: &str) -> Result<UriReference, UriParserError> {
+
+    // reference only has a scheme if it is followed by ':'
+    let mut lookahead = parser.clone();
+    let scheme = match parse_scheme(&mut lookahead) {
+        Ok(scheme) if Some(&':') == lookahead.peek() => {
+            lookahead.next();
+            *parser = lookahead;
+            Some(scheme)
+        },
+        _ => None
+    };
+
+    // reference only has an authority if it begins with '//'; without a scheme of its own, the base scheme decides how it is read
+    let authority = if parser.as_str().starts_with("//") {
+        parser.next();
+        parser.next();
+        if is_unix_socket_scheme(scheme.as_deref().unwrap_or(base_scheme)) {
+            Some(parse_socket_authority(parser)?)
+        } else {
+            Some(parse_authority(parser)?)
+        }
+    } else {
+        None
+    };
+
+    // reference with a scheme, but without an authority or absolute path, has an opaque path
+    if scheme.is_some() && authority.is_none() && is_opaque_path_start(parser) {
+        let opaque = parse_opaque_path(parser)?;
+        let query = parse_query(parser)?;
+        let fragment = parse_fragment(parser)?;
+        return Ok(UriReference {
+            scheme: scheme,
+            authority: None,
+            opaque: Some(opaque),
+            absolute: false,
+            segments: Vec::new(),
+            trailing_slash: false,
+            query: query,
+            fragment: fragment
+        });
+    }
+
+    // path is absolute if it begins with '/' (or '\'); otherwise, it is relative to the base path
+    let absolute = (Some(&'/') == parser.peek()) || (Some(&'\\') == parser.peek());
+    let (segments, trailing_slash) = if absolute || authority.is_some() {
+        parse_path(parser)?
+    } else {
+        parse_relative_path(parser)?
+    };
+    let query = parse_query(parser)?;
+    let fragment = parse_fragment(parser)?;
+    return Ok(UriReference {
+        scheme: scheme,
+        authority: authority,
+        opaque: None,
+        absolute: absolute,
+        segments: segments,
+        trailing_slash: trailing_slash,
+        query: query,
+        fragment: fragment
+    });
+}
+
+pub fn is_opaque_path_start(parser: &UriChars) -> bool {
+
+    // opaque path cannot begin with '/' (or '\'), but it may be empty (e.g. 'mailto:?to=bob')
+    match parser.peek() {
+        Some(&'/') | Some(&'\\') => return false,
+        _ => return true
+    }
+}
+
+pub fn parse_opaque_path(parser: &mut UriChars) -> Result<String, UriParserError> {
+
+    // opaque path is kept encoded, since its interpretation depends on the scheme
+    let mut buffer = String::new();
+    loop {
+        match parser.peek() {
+            Some(&'%') => {
+
+                // escape sequence must be followed by two hex digits
+                let bytes = parser.as_str().as_bytes();
+                if (bytes.len() < 3) || !bytes[1].is_ascii_hexdigit() || !bytes[2].is_ascii_hexdigit() {
+                    return Err(unexpected(parser, UriErrorKind::InvalidEncoding, UriComponent::Path));
+                }
+                for _ in 0..3 {
+                    if let Some(c) = parser.next() {
+                        buffer.push(c);
+                    }
+                }
+            },
+            Some(&c) if is_path_char(c) || (c == '/') => {
+                parser.next();
+
+                // valid character, keep parsing
+                if c.is_ascii() {
+                    buffer.push(c);
+                } else {
+                    buffer.push_str(&encode_opaque_path(c.encode_utf8(&mut [0; 4])));
+                }
+            },
+            Some(&'?') | Some(&'#') | None => return Ok(buffer),
+            Some(_) => return Err(unexpected(parser, UriErrorKind::InvalidPath, UriComponent::Path))
+        }
+    }
 }
 
+pub fn parse_path(parser: &mut UriChars) -> Result<(Vec<Segment>, bool), UriParserError> {
+
+    // path must either be empty or begin with '/' (or '\')
+    match parser.peek() {
+        Some(&'/') | Some(&'\\') => {
+            parser.next();
+        },
+        Some(&'?') | Some(&'#') | None => return Ok((Vec::new(), false)),
+        Some(_) => return Err(unexpected(parser, UriErrorKind::InvalidPath, UriComponent::Path))
+    }
+    return parse_segments(parser);
+}
+
+pub fn parse_relative_path(parser: &mut UriChars) -> Result<(Vec<Segment>, bool), UriParserError> {
+
+    // relative path must either be empty or begin with a segment
+    match parser.peek() {
+        Some(&'?') | Some(&'#') | None => return Ok((Vec::new(), false)),
+        Some(&'/') | Some(&'\\') => return Err(unexpected(parser, UriErrorKind::InvalidPath, UriComponent::Path)),
+        Some(_) => ()
+    }
+    return parse_segments(parser);
+}
+
+pub fn remove_dot_segments(segments: Vec<Segment>) -> Vec<Segment> {
+
+    // an empty last segment stands for a trailing '/'; dot segments at the end leave one behind
+    let count = segments.len();
+    let mut result = Vec::new();
+    for (index, segment) in segments.into_iter().enumerate() {
+        let last = (index + 1) == count;
+
+        // segment with matrix parameters is never a dot segment
+        if segment.params.is_empty() && ((segment.name == ".") || (segment.name == "..")) {
+            if segment.name == ".." {
+                result.pop();
+            }
+            if last {
+                result.push(Segment::new(String::new()));
+            }
+        } else {
+            result.push(segment);
+        }
+    }
+    return result;
+}
+
+fn parse_segments(parser: &mut UriChars) -> Result<(Vec<Segment>, bool), UriParserError> {
+    let mut segments = Vec::new();
+    let mut buffer = String::new();
+    let mut start = parser.offset();
+
+    // parse path segments
+    loop {
+        match parser.peek() {
+            Some(&'%') => {
+                parser.next();
+
+                // potentially valid character, keep parsing
+                buffer.push('%');
+            },
+            Some(&c) if is_path_char(c) => {
+                parser.next();
+
+                // valid character, keep parsing
+                buffer.push(c);
+            },
+            Some(&'/') | Some(&'\\') => {
+                parser.next();
+
+                // part before '/' (or '\') is a segment
+                segments.push(parse_segment(&buffer, start)?);
+                buffer = String::new();
+                start = parser.offset();
+            },
+            Some(&'?') | Some(&'#') | None => {
+
+                // an empty last segment means the path ended with '/'
+                if buffer.is_empty() {
+                    return Ok((segments, true));
+                }
+                segments.push(parse_segment(&buffer, start)?);
+                return Ok((segments, false));
+            },
+            Some(_) => return Err(unexpected(parser, UriErrorKind::InvalidPath, UriComponent::Path))
+        }
+    }
+}
+
+fn parse_segment(text: &str, start: usize) -> Result<Segment, UriParserError> {
+
+    // ';' starts a matrix parameter and the first '=' separates its key from its value
+    let mut pieces = text.split(';');
+    let name_text = pieces.next().unwrap_or_default();
+    let name = uri_decode(name_text, start, UriComponent::Path)?;
+    let mut params = Vec::new();
+    let mut offset = start + name_text.len() + 1;
+    for piece in pieces {
+        params.push(match piece.find('=') {
+            Some(index) => (
+                uri_decode(&piece[..index], offset, UriComponent::Path)?,
+                Some(uri_decode(&piece[(index + 1)..], offset + index + 1, UriComponent::Path)?)
+            ),
+            None => (uri_decode(piece, offset, UriComponent::Path)?, None)
+        });
+        offset += piece.len() + 1;
+    }
+    return Ok(Segment::new_with_params(name, params));
+}
+
+pub fn parse_query(parser: &mut UriChars) -> Result<Option<Params>, UriParserError> {
+
+    // query must begin with '?'
+    match parser.peek() {
+        Some(&'?') => {
+            parser.next();
+        },
+        Some(&'#') | None => return Ok(None),
+        Some(_) => return Err(unexpected(parser, UriErrorKind::InvalidQuery, UriComponent::Query))
+    }
+    let mut buffer = String::new();
+    let start = parser.offset();
+
+    // collect query text up to the fragment
+    loop {
+        match parser.peek() {
+            Some(&c) if (c == '%') || (c == '+') || is_path_char(c) || (c == '/') || (c == '?') => {
+                parser.next();
+
+                // potentially valid character, keep parsing
+                buffer.push(c);
+            },
+            Some(&'#') | None => break,
+            Some(_) => return Err(unexpected(parser, UriErrorKind::InvalidQuery, UriComponent::Query))
+        }
+    }
+
+    // parameters are split at '&' and '='; unlike form bodies, '+' is not a space here
+    let params = form_urlencoded::parse_pairs(&buffer, Separator::Ampersand, false).map_err(|(part_start, part_end, error)| {
+        return decode_error(&buffer[part_start..part_end], start + part_start, UriComponent::Query, error);
+    })?;
+    return Ok(Some(params));
+}
+
+pub fn parse_fragment(parser: &mut UriChars) -> Result<Option<String>, UriParserError> {
+
+    // fragment must begin with '#'
+    match parser.peek() {
+        Some(&'#') => {
+            parser.next();
+        },
+        None => return Ok(None),
+        Some(_) => return Err(unexpected(parser, UriErrorKind::InvalidFragment, UriComponent::Fragment))
+    }
+    let mut decode = false;
+    let mut buffer = String::new();
+    let start = parser.offset();
+
+    // parse fragment
+    loop {
+        match parser.peek() {
+            Some(&'%') => {
+                parser.next();
+                decode = true;
+
+                // potentially valid character, keep parsing
+                buffer.push('%');
+            },
+            Some(&c) if is_path_char(c) || (c == '/') || (c == '?') => {
+                parser.next();
+
+                // valid character, keep parsing
+                buffer.push(c);
+            },
+            None => {
+                return Ok(Some(if decode {
+                    uri_decode(&buffer, start, UriComponent::Fragment)?
+                } else {
+                    buffer
+                }));
+            },
+            Some(_) => return Err(unexpected(parser, UriErrorKind::InvalidFragment, UriComponent::Fragment))
+        }
+    }
+}
+
+fn to_host(name: String, start: usize) -> Result<Host, UriParserError> {
+
+    // hostname consisting of four decimal octets is an IPv4 address
+    if let Ok(address) = name.parse::<Ipv4Addr>() {
+        return Ok(Host::Ipv4(address));
+    }
+
+    // internationalized hostname is stored in its ASCII form
+    match domain_to_ascii(&name, start)? {
+        Some(ascii) => return Ok(Host::Domain(ascii)),
+        None => return Ok(Host::Domain(name))
+    }
+}
+
+pub fn domain_to_ascii(name: &str, start: usize) -> Result<Option<String>, UriParserError> {
+
+    // only hostnames with non-ASCII characters or punycode labels are processed (UTS #46)
+    if name.is_ascii() && !name.split('.').any(is_punycode_label) {
+        return Ok(None);
+    }
+    match idna::domain_to_ascii(name) {
+        Ok(ascii) => {
+
+            // punycode hostname is kept as is once validated
+            if name.is_ascii() {
+                return Ok(None);
+            }
+            return Ok(Some(ascii));
+        },
+        Err(_) => {
+
+            // report the first label that fails on its own, otherwise the whole hostname
+            let mut offset = start;
+            for label in name.split('.') {
+                if idna::domain_to_ascii(label).is_err() {
+                    return Err(UriParserError::new(UriErrorKind::InvalidDomainName, UriComponent::Host, offset, label.chars().next()));
+                }
+                offset += label.len() + 1;
+            }
+            return Err(UriParserError::new(UriErrorKind::InvalidDomainName, UriComponent::Host, start, name.chars().next()));
+        }
+    }
+}
+
+fn is_punycode_label(label: &str) -> bool {
+    return (label.len() >= 4) && label[..4].eq_ignore_ascii_case("xn--");
+}
+
+pub fn is_authority_char(c: char) -> bool {
+    return c.is_ascii_alphanumeric()
+        || ('$'..='.').contains(&c)     // one of: $%&'()*+,-.
+        || (c == '!')
+        || (c == ';')
+        || (c == '=')
+        || (c == '_')
+        || (c == '~')
+        || c.is_numeric()
+        || c.is_alphabetic();
+}
+
+pub fn is_path_char(c: char) -> bool {
+    return is_authority_char(c) || (c == ':') || (c == '@');
+}
+
+fn unexpected(parser: &UriChars, kind: UriErrorKind, component: UriComponent) -> UriParserError {
+    return UriParserError::new(kind, component, parser.offset(), parser.peek().cloned());
+}
+
+fn encoded(text: &str, start: usize, kind: UriErrorKind, component: UriComponent) -> UriParserError {
+
+    // point at the first escape sequence, since it is the reason the text is rejected
+    let index = text.find('%').unwrap_or(0);
+    return UriParserError::new(kind, component, start + index, text[index..].chars().next());
+}
+
+fn invalid_port(text: &str, start: usize) -> UriParserError {
+
+    // point at the first non-digit; a port made only of digits is out of range
+    let index = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(0);
+    return UriParserError::new(UriErrorKind::InvalidPortNumber, UriComponent::Port, start + index, text[index..].chars().next());
+}
+
+fn uri_decode(text: &str, start: usize, component: UriComponent) -> Result<String, UriParserError> {
+    return decode(text).map_err(|error| decode_error(text, start, component, error));
+}
+
+fn decode_error(text: &str, start: usize, component: UriComponent, error: DecodeError) -> UriParserError {
+    match error {
+        DecodeError::InvalidEscape(index) => {
+            return UriParserError::new(UriErrorKind::InvalidEncoding, component, start + index, Some('%'));
+        },
+        DecodeError::InvalidUtf8 => {
+            return encoded(text, start, UriErrorKind::InvalidEncoding, component);
+        }
+    }
+}
@@ -0,0 +1,533 @@
+/*
+ * RustyPlug - a rust module with a fluid interface for building requests to sockets
+ *
+ * Copyright (C) 2016 Steve G. Bjorg
+ *
+ * For community documentation and downloads visit mindtouch.com;
+ * please review the licensing section.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use encoding::{decode, encode_reserved, encode_unreserved};
+use plug::{Plug, PlugParserError};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TemplateValue {
+    String(String),
+    List(Vec<String>),
+    Map(Vec<(String, String)>)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UriTemplate {
+    parts: Vec<TemplatePart>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum UriTemplateError {
+    UnclosedExpression(usize),
+    InvalidOperator(usize),
+    InvalidVariable(usize),
+    InvalidPrefix(usize)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TemplatePart {
+    Literal(String),
+    Expression(Operator, Vec<VarSpec>)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operator {
+    Simple,
+    Reserved,
+    Fragment,
+    Label,
+    Path,
+    Parameter,
+    Query,
+    Continuation
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct VarSpec {
+    name: String,
+    prefix: Option<usize>,
+    explode: bool
+}
+
+impl UriTemplate {
+    pub fn parse(template: &str) -> Result<UriTemplate, UriTemplateError> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.char_indices().peekable();
+        while let Some((offset, c)) = chars.next() {
+            if c != '{' {
+
+                // literal characters that are not allowed in a URI are encoded
+                if c == '%' {
+                    literal.push(c);
+                } else {
+                    literal.push_str(&encode_reserved(c.encode_utf8(&mut [0; 4])));
+                }
+                continue;
+            }
+            if !literal.is_empty() {
+                parts.push(TemplatePart::Literal(literal));
+                literal = String::new();
+            }
+
+            // expression ends at the first '}'
+            let end = match template[offset..].find('}') {
+                Some(index) => offset + index,
+                None => return Err(UriTemplateError::UnclosedExpression(offset))
+            };
+            let mut start = offset + 1;
+            let operator = match template[start..].chars().next() {
+                Some('+') => Operator::Reserved,
+                Some('#') => Operator::Fragment,
+                Some('.') => Operator::Label,
+                Some('/') => Operator::Path,
+                Some(';') => Operator::Parameter,
+                Some('?') => Operator::Query,
+                Some('&') => Operator::Continuation,
+
+                // operators reserved for future extensions (RFC 6570, section 2.2)
+                Some('=') | Some(',') | Some('!') | Some('@') | Some('|') => return Err(UriTemplateError::InvalidOperator(start)),
+                _ => Operator::Simple
+            };
+            if operator != Operator::Simple {
+                start += 1;
+            }
+            let mut variables = Vec::new();
+            for varspec in template[start..end].split(',') {
+                variables.push(parse_varspec(varspec, start)?);
+                start += varspec.len() + 1;
+            }
+            parts.push(TemplatePart::Expression(operator, variables));
+
+            // skip past the expression
+            while let Some(&(index, _)) = chars.peek() {
+                if index > end {
+                    break;
+                }
+                chars.next();
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(TemplatePart::Literal(literal));
+        }
+        return Ok(UriTemplate {
+            parts: parts
+        });
+    }
+
+    pub fn get_variable_names(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        for part in self.parts.iter() {
+            if let TemplatePart::Expression(_, ref variables) = *part {
+                for variable in variables.iter() {
+                    if !names.contains(&variable.name.as_str()) {
+                        names.push(variable.name.as_str());
+                    }
+                }
+            }
+        }
+        return names;
+    }
+
+    pub fn expand_to_string(&self, variables: &HashMap<String, TemplateValue>) -> String {
+        return expand_parts(&self.parts, variables);
+    }
+
+    pub fn expand(&self, variables: &HashMap<String, TemplateValue>) -> Result<Plug, PlugParserError> {
+
+        // trailing path, query and fragment expressions are applied with the plug builders;
+        // everything before them is expanded as text and parsed
+        let mut tail_start = self.parts.len();
+        let mut stage: Option<Stage> = None;
+        for (index, part) in self.parts.iter().enumerate().rev() {
+            match *part {
+                TemplatePart::Expression(operator, _) => {
+
+                    // expressions must go from path to query to a single fragment
+                    match (operator.get_stage(), stage) {
+                        (Some(operator_stage), None) => {
+                            stage = Some(operator_stage);
+                            tail_start = index;
+                        },
+                        (Some(operator_stage), Some(stage_so_far)) if (operator_stage < stage_so_far) || ((operator_stage == stage_so_far) && (stage_so_far != Stage::Fragment)) => {
+                            stage = Some(operator_stage);
+                            tail_start = index;
+                        },
+                        _ => break
+                    }
+                },
+                TemplatePart::Literal(_) => break
+            }
+        }
+
+        // base with a query or a fragment already cannot take further path expressions
+        let mut base = expand_parts(&self.parts[..tail_start], variables);
+        while tail_start < self.parts.len() {
+            let base_stage = if base.contains('#') {
+                Stage::Fragment
+            } else if base.contains('?') {
+                Stage::Query
+            } else {
+                Stage::Path
+            };
+            match self.parts[tail_start] {
+                TemplatePart::Expression(operator, _) if operator.get_stage() >= Some(base_stage) => break,
+                _ => {
+                    base.push_str(&expand_parts(&self.parts[tail_start..(tail_start + 1)], variables));
+                    tail_start += 1;
+                }
+            }
+        }
+        let mut plug = Plug::parse(&base)?;
+        for part in self.parts[tail_start..].iter() {
+            if let TemplatePart::Expression(operator, ref specs) = *part {
+                plug = apply_expression(plug, operator, specs, variables);
+            }
+        }
+        return Ok(plug);
+    }
+}
+
+impl fmt::Display for UriTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for part in self.parts.iter() {
+            match *part {
+                TemplatePart::Literal(ref literal) => f.write_str(literal)?,
+                TemplatePart::Expression(operator, ref variables) => {
+                    f.write_str("{")?;
+                    f.write_str(operator.get_symbol())?;
+                    for (index, variable) in variables.iter().enumerate() {
+                        if index > 0 {
+                            f.write_str(",")?;
+                        }
+                        f.write_str(&variable.name)?;
+                        if let Some(prefix) = variable.prefix {
+                            write!(f, ":{}", prefix)?;
+                        }
+                        if variable.explode {
+                            f.write_str("*")?;
+                        }
+                    }
+                    f.write_str("}")?;
+                }
+            }
+        }
+        return Ok(());
+    }
+}
+
+impl FromStr for UriTemplate {
+    type Err = UriTemplateError;
+
+    fn from_str(template: &str) -> Result<UriTemplate, UriTemplateError> {
+        return UriTemplate::parse(template);
+    }
+}
+
+impl<'a> From<&'a str> for TemplateValue {
+    fn from(value: &'a str) -> TemplateValue {
+        return TemplateValue::String(value.into());
+    }
+}
+
+impl From<String> for TemplateValue {
+    fn from(value: String) -> TemplateValue {
+        return TemplateValue::String(value);
+    }
+}
+
+impl From<Vec<String>> for TemplateValue {
+    fn from(values: Vec<String>) -> TemplateValue {
+        return TemplateValue::List(values);
+    }
+}
+
+impl From<Vec<(String, String)>> for TemplateValue {
+    fn from(pairs: Vec<(String, String)>) -> TemplateValue {
+        return TemplateValue::Map(pairs);
+    }
+}
+
+impl fmt::Display for UriTemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UriTemplateError::UnclosedExpression(offset) => return write!(f, "unclosed expression at offset {}", offset),
+            UriTemplateError::InvalidOperator(offset) => return write!(f, "invalid operator at offset {}", offset),
+            UriTemplateError::InvalidVariable(offset) => return write!(f, "invalid variable name at offset {}", offset),
+            UriTemplateError::InvalidPrefix(offset) => return write!(f, "invalid prefix modifier at offset {}", offset)
+        }
+    }
+}
+
+impl Error for UriTemplateError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+enum Stage {
+    Path,
+    Query,
+    Fragment
+}
+
+impl Operator {
+    fn get_symbol(self) -> &'static str {
+        match self {
+            Operator::Simple => return "",
+            Operator::Reserved => return "+",
+            Operator::Fragment => return "#",
+            Operator::Label => return ".",
+            Operator::Path => return "/",
+            Operator::Parameter => return ";",
+            Operator::Query => return "?",
+            Operator::Continuation => return "&"
+        }
+    }
+
+    fn get_stage(self) -> Option<Stage> {
+        match self {
+            Operator::Path => return Some(Stage::Path),
+            Operator::Query | Operator::Continuation => return Some(Stage::Query),
+            Operator::Fragment => return Some(Stage::Fragment),
+            _ => return None
+        }
+    }
+
+    // expansion behavior per operator (RFC 6570, appendix A): first, separator, named, if-empty, allow-reserved
+    fn get_behavior(self) -> (&'static str, &'static str, bool, &'static str, bool) {
+        match self {
+            Operator::Simple => return ("", ",", false, "", false),
+            Operator::Reserved => return ("", ",", false, "", true),
+            Operator::Fragment => return ("#", ",", false, "", true),
+            Operator::Label => return (".", ".", false, "", false),
+            Operator::Path => return ("/", "/", false, "", false),
+            Operator::Parameter => return (";", ";", true, "", false),
+            Operator::Query => return ("?", "&", true, "=", false),
+            Operator::Continuation => return ("&", "&", true, "=", false)
+        }
+    }
+}
+
+fn parse_varspec(text: &str, start: usize) -> Result<VarSpec, UriTemplateError> {
+
+    // modifier is either ':' followed by a prefix length, or '*'
+    let (name, prefix, explode) = if let Some(name) = text.strip_suffix('*') {
+        (name, None, true)
+    } else if let Some(index) = text.find(':') {
+        let length = &text[(index + 1)..];
+        if length.is_empty() || (length.len() > 4) || !length.bytes().all(|b| b.is_ascii_digit()) || length.starts_with('0') {
+            return Err(UriTemplateError::InvalidPrefix(start + index + 1));
+        }
+        (&text[..index], length.parse::<usize>().ok(), false)
+    } else {
+        (text, None, false)
+    };
+
+    // variable name consists of letters, digits, '_' and escapes, with '.' between them
+    let bytes = name.as_bytes();
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'%' if ((index + 2) < bytes.len()) && bytes[index + 1].is_ascii_hexdigit() && bytes[index + 2].is_ascii_hexdigit() => index += 3,
+            b'.' if (index > 0) && ((index + 1) < bytes.len()) && (bytes[index - 1] != b'.') => index += 1,
+            b if b.is_ascii_alphanumeric() || (b == b'_') => index += 1,
+            _ => return Err(UriTemplateError::InvalidVariable(start + index))
+        }
+    }
+    if name.is_empty() {
+        return Err(UriTemplateError::InvalidVariable(start));
+    }
+    return Ok(VarSpec {
+        name: name.into(),
+        prefix: prefix,
+        explode: explode
+    });
+}
+
+fn expand_parts(parts: &[TemplatePart], variables: &HashMap<String, TemplateValue>) -> String {
+    let mut buffer = String::new();
+    for part in parts.iter() {
+        match *part {
+            TemplatePart::Literal(ref literal) => buffer.push_str(literal),
+            TemplatePart::Expression(operator, ref specs) => expand_expression(&mut buffer, operator, specs, variables)
+        }
+    }
+    return buffer;
+}
+
+fn expand_expression(buffer: &mut String, operator: Operator, specs: &[VarSpec], variables: &HashMap<String, TemplateValue>) {
+    let (first, separator, named, if_empty, allow_reserved) = operator.get_behavior();
+    let mut is_first = true;
+    for spec in specs.iter() {
+        let value = match get_defined(variables, &spec.name) {
+            Some(value) => value,
+            None => continue
+        };
+        buffer.push_str(if is_first { first } else { separator });
+        is_first = false;
+        match *value {
+            TemplateValue::String(ref text) => {
+                if named {
+                    buffer.push_str(&spec.name);
+                    if text.is_empty() {
+                        buffer.push_str(if_empty);
+                        continue;
+                    }
+                    buffer.push('=');
+                }
+                buffer.push_str(&encode(truncate(text, spec.prefix), allow_reserved));
+            },
+            TemplateValue::List(ref items) if spec.explode => {
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        buffer.push_str(separator);
+                    }
+                    if named {
+                        buffer.push_str(&spec.name);
+                        if item.is_empty() {
+                            buffer.push_str(if_empty);
+                            continue;
+                        }
+                        buffer.push('=');
+                    }
+                    buffer.push_str(&encode(item, allow_reserved));
+                }
+            },
+            TemplateValue::Map(ref pairs) if spec.explode => {
+                for (index, (key, item)) in pairs.iter().enumerate() {
+                    if index > 0 {
+                        buffer.push_str(separator);
+                    }
+                    buffer.push_str(&encode(key, allow_reserved));
+                    if named && item.is_empty() {
+                        buffer.push_str(if_empty);
+                        continue;
+                    }
+                    buffer.push('=');
+                    buffer.push_str(&encode(item, allow_reserved));
+                }
+            },
+            _ => {
+                if named {
+                    buffer.push_str(&spec.name);
+                    buffer.push('=');
+                }
+                let items: Vec<String> = join_composite(value).iter().map(|item| encode(item, allow_reserved)).collect();
+                buffer.push_str(&items.join(","));
+            }
+        }
+    }
+}
+
+fn apply_expression(mut plug: Plug, operator: Operator, specs: &[VarSpec], variables: &HashMap<String, TemplateValue>) -> Plug {
+    let mut fragment: Vec<String> = Vec::new();
+    for spec in specs.iter() {
+        let value = match get_defined(variables, &spec.name) {
+            Some(value) => value,
+            None => continue
+        };
+        match operator {
+            Operator::Path => {
+
+                // each expanded value is a segment; a trailing '/' in the base leaves an empty segment behind
+                let mut segments = match *value {
+                    TemplateValue::String(ref text) => vec![truncate(text, spec.prefix).to_string()],
+                    TemplateValue::List(ref items) if spec.explode => items.clone(),
+                    TemplateValue::Map(ref pairs) if spec.explode => pairs.iter().map(|(key, item)| format!("{}={}", key, item)).collect(),
+                    _ => vec![join_composite(value).join(",")]
+                };
+                if plug.get_trailing_slash() {
+                    segments.insert(0, String::new());
+                }
+                plug = plug.without_trailing_slash().at(segments);
+            },
+            Operator::Query | Operator::Continuation => {
+                match *value {
+                    TemplateValue::String(ref text) => plug = plug.with(spec.name.clone(), truncate(text, spec.prefix).into()),
+                    TemplateValue::List(ref items) if spec.explode => {
+                        for item in items.iter() {
+                            plug = plug.with(spec.name.clone(), item.clone());
+                        }
+                    },
+                    TemplateValue::Map(ref pairs) if spec.explode => {
+                        for (key, item) in pairs.iter() {
+                            plug = plug.with(key.clone(), item.clone());
+                        }
+                    },
+                    _ => plug = plug.with(spec.name.clone(), join_composite(value).join(","))
+                }
+            },
+            _ => {
+                match *value {
+                    TemplateValue::String(ref text) => fragment.push(truncate(text, spec.prefix).into()),
+                    TemplateValue::Map(ref pairs) if spec.explode => fragment.extend(pairs.iter().map(|(key, item)| format!("{}={}", key, item))),
+                    _ => fragment.extend(join_composite(value))
+                }
+            }
+        }
+    }
+    if (operator == Operator::Fragment) && !fragment.is_empty() {
+
+        // reserved expansion keeps escapes in the value, so they are decoded before the builder encodes the fragment;
+        // escapes that do not decode to text are kept as written
+        let text = encode_reserved(&fragment.join(","));
+        plug = plug.with_fragment(&decode(&text).unwrap_or(text));
+    }
+    return plug;
+}
+
+fn get_defined<'a>(variables: &'a HashMap<String, TemplateValue>, name: &str) -> Option<&'a TemplateValue> {
+
+    // empty lists and maps are treated as undefined (RFC 6570, section 2.3)
+    match variables.get(name) {
+        Some(TemplateValue::List(items)) if items.is_empty() => return None,
+        Some(TemplateValue::Map(pairs)) if pairs.is_empty() => return None,
+        value => return value
+    }
+}
+
+fn join_composite(value: &TemplateValue) -> Vec<String> {
+    match *value {
+        TemplateValue::String(ref text) => return vec![text.clone()],
+        TemplateValue::List(ref items) => return items.clone(),
+        TemplateValue::Map(ref pairs) => return pairs.iter().flat_map(|(key, item)| vec![key.clone(), item.clone()]).collect()
+    }
+}
+
+fn truncate(text: &str, prefix: Option<usize>) -> &str {
+    match prefix.and_then(|length| text.char_indices().nth(length)) {
+        Some((index, _)) => return &text[..index],
+        None => return text
+    }
+}
+
+fn encode(text: &str, allow_reserved: bool) -> String {
+
+    // reserved expansion passes through reserved characters and existing escapes
+    if allow_reserved {
+        return encode_reserved(text);
+    }
+    return encode_unreserved(text);
+}